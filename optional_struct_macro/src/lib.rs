@@ -10,7 +10,10 @@ pub fn optional_struct(
     attr: proc_macro::TokenStream,
     input: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    let out = opt_struct::opt_struct(attr.into(), input.into());
+    let out = match opt_struct::opt_struct(attr.into(), input.into()) {
+        Ok(out) => out,
+        Err(e) => return e.to_compile_error().into(),
+    };
     let original = out.original;
     let generated = out.generated;
     proc_macro::TokenStream::from(quote! {
@@ -19,3 +22,32 @@ pub fn optional_struct(
         #generated
     })
 }
+
+#[proc_macro_derive(
+    OptionalStruct,
+    attributes(
+        optional_params,
+        optional_rename,
+        optional_nested,
+        optional_skip_wrap,
+        optional_wrap,
+        optional_serde_skip_none,
+        optional_is_option,
+        optional_not_option,
+        optional_attr,
+        optional_skip_attr,
+        optional_include,
+        optional_exclude,
+        optional_default,
+        optional_merge,
+        optional_validate,
+        optional_env,
+        optional_clap
+    )
+)]
+pub fn optional_struct_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    match opt_struct::opt_struct_derive(input.into()) {
+        Ok(generated) => generated.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
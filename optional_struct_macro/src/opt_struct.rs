@@ -1,27 +1,159 @@
 use std::collections::HashSet;
 
-use proc_macro2::{TokenStream, TokenTree};
-use quote::{format_ident, quote};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, quote_spanned};
 use syn::parse::{Parse, ParseStream};
 use syn::punctuated::Punctuated;
-use syn::token::Comma;
+use syn::spanned::Spanned;
 use syn::{
-    parse_quote, spanned::Spanned, Attribute, Data, DeriveInput, Field, Fields, Ident, Path, Token,
-    Type, Visibility,
+    parse_quote, parse_quote_spanned, Attribute, Data, DeriveInput, Field, Fields, Ident, Meta,
+    Path, Token, Type, TypePath, Variant, Visibility, WherePredicate,
 };
 
 const RENAME_ATTRIBUTE: &str = "optional_rename";
 const SKIP_WRAP_ATTRIBUTE: &str = "optional_skip_wrap";
 const WRAP_ATTRIBUTE: &str = "optional_wrap";
 const SERDE_SKIP_SERIALIZING_NONE: &str = "optional_serde_skip_none";
+const IS_OPTION_ATTRIBUTE: &str = "optional_is_option";
+const NOT_OPTION_ATTRIBUTE: &str = "optional_not_option";
+const NESTED_ATTRIBUTE: &str = "optional_nested";
+const ATTR_ATTRIBUTE: &str = "optional_attr";
+const SKIP_ATTR_ATTRIBUTE: &str = "optional_skip_attr";
+const INCLUDE_ATTRIBUTE: &str = "optional_include";
+const EXCLUDE_ATTRIBUTE: &str = "optional_exclude";
+const DEFAULT_ATTRIBUTE: &str = "optional_default";
+const MERGE_ATTRIBUTE: &str = "optional_merge";
+const VALIDATE_ATTRIBUTE: &str = "optional_validate";
+const ENV_ATTRIBUTE: &str = "optional_env";
+const CLAP_ATTRIBUTE: &str = "optional_clap";
+const HELPER_ATTRIBUTES: &[&str] = &[
+    RENAME_ATTRIBUTE,
+    NESTED_ATTRIBUTE,
+    SKIP_WRAP_ATTRIBUTE,
+    WRAP_ATTRIBUTE,
+    SERDE_SKIP_SERIALIZING_NONE,
+    IS_OPTION_ATTRIBUTE,
+    NOT_OPTION_ATTRIBUTE,
+    ATTR_ATTRIBUTE,
+    SKIP_ATTR_ATTRIBUTE,
+    INCLUDE_ATTRIBUTE,
+    EXCLUDE_ATTRIBUTE,
+    DEFAULT_ATTRIBUTE,
+    MERGE_ATTRIBUTE,
+    VALIDATE_ATTRIBUTE,
+    ENV_ATTRIBUTE,
+    CLAP_ATTRIBUTE,
+];
+const PARAMS_ATTRIBUTE: &str = "optional_params";
+const DERIVE_NAME: &str = "OptionalStruct";
 const CFG_ATTRIBUTE: &str = "cfg";
+const CFG_ATTR_ATTRIBUTE: &str = "cfg_attr";
+
+const NAME_PARAMETER: &str = "name";
+const DEFAULT_WRAP_PARAMETER: &str = "default_wrap";
+const DERIVE_PARAMETER: &str = "derive";
+const VISIBILITY_PARAMETER: &str = "vis";
+const FIELD_VISIBILITY_PARAMETER: &str = "field_vis";
+const INHERIT_VISIBILITY: &str = "inherit";
+const BOUND_PARAMETER: &str = "bound";
+const DEFAULT_PARAMETER: &str = "default";
+const ENV_PARAMETER: &str = "env";
+const ENV_SEPARATOR_PARAMETER: &str = "env_separator";
+const CLAP_PARAMETER: &str = "clap";
 
 struct FieldOptions {
     wrapping_behavior: bool,
+    is_base_opt: bool,
     serde_skip: bool,
-    cfg_attribute: Option<Attribute>,
-    new_type: Option<TokenTree>,
+    /// All the `cfg` attributes of the field, to be put in front of the generated code.
+    cfg_attribute: TokenStream,
+    new_type: Option<Type>,
     field_ident: TokenStream,
+    field_index: usize,
+    /// Fields of enum variants cannot be reached through `self.field`: they are bound by the
+    /// enclosing match arm instead (see `variant_pattern`).
+    in_variant: bool,
+    /// Whether the field is part of the view currently generated (see `optional_include` and
+    /// `optional_exclude`).
+    in_view: bool,
+    /// The expression given with `optional_default`, used by `build_with_defaults`.
+    default_value: Option<TokenStream>,
+    /// The function merging values, given with `optional_merge`, used instead of replacing them.
+    merge_with: Option<TokenStream>,
+    /// The checks given with `optional_validate`, run by the `Validate` impl of the base.
+    validations: Vec<Validation>,
+    env_variable: EnvVariable,
+    clap_options: ClapOptions,
+    attribute_forwarding: AttributeForwarding,
+}
+
+/// The content of `optional_clap`: either `skip`, or calls to the methods of `clap::Arg`, e.g.
+/// `short = 'p'` for `.short('p')`.
+#[derive(Default)]
+struct ClapOptions {
+    skip: bool,
+    calls: Vec<(Ident, TokenStream)>,
+}
+
+impl Parse for ClapOptions {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut options = ClapOptions::default();
+        while !input.is_empty() {
+            let method: Ident = input.parse()?;
+            if method == "skip" {
+                options.skip = true;
+            } else {
+                input.parse::<Token![=]>()?;
+                let mut value = TokenStream::new();
+                while !input.is_empty() && !input.peek(Token![,]) {
+                    value.extend([input.parse::<proc_macro2::TokenTree>()?]);
+                }
+                if value.is_empty() {
+                    return Err(input.error(format!("expected a value for `{method}`")));
+                }
+                options.calls.push((method, value));
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// The variable a field is read from by `FromEnv`, see `optional_env`.
+enum EnvVariable {
+    /// Named after the field, e.g. `{prefix}_LOG_FILE`.
+    Default,
+    Named(syn::LitStr),
+    Skip,
+}
+
+/// One of the checks of `optional_validate`, i.e. a call to `function(&value, extra_args...)` (the
+/// extra arguments include the leading comma).
+struct Validation {
+    function: TokenStream,
+    extra_args: TokenStream,
+}
+
+impl FieldOptions {
+    /// The expression used to reach this field from `root` (`self`, `t` or `v`) in the generated
+    /// code. `by_ref` tells whether the match arm binding the field of an enum variant did so by
+    /// reference.
+    fn access(&self, root: &str, by_ref: bool) -> TokenStream {
+        if self.in_variant {
+            let binding = format_ident!("{root}_{}", self.field_index);
+            if by_ref {
+                quote! { (*#binding) }
+            } else {
+                quote! { #binding }
+            }
+        } else {
+            let root = format_ident!("{root}");
+            let ident = &self.field_ident;
+            quote! { #root.#ident }
+        }
+    }
 }
 
 trait OptionalFieldVisitor {
@@ -32,36 +164,102 @@ trait OptionalFieldVisitor {
         new_field: &mut Field,
         field_options: &FieldOptions,
     );
+
+    /// Called instead of `visit` for the fields left out of the view being generated.
+    fn visit_excluded(&mut self, _global_options: &GlobalOptions, _field_options: &FieldOptions) {}
+
+    /// Called once all the fields of an enum variant have been visited.
+    fn end_variant(
+        &mut self,
+        _global_options: &GlobalOptions,
+        _orig_name: &Ident,
+        _variant: &Variant,
+    ) {
+    }
+}
+
+/// The name of a field in the paths returned by `missing_fields`, e.g. `bar` or `0`.
+fn field_name(field_options: &FieldOptions) -> String {
+    let name = field_options.field_ident.to_string();
+    name.strip_prefix("r#").map(str::to_owned).unwrap_or(name)
+}
+
+/// The pattern binding every field of an enum variant to `<root>_<index>`, e.g.
+/// `Path::Variant { a: t_0, b: t_1 }`. Fields are always referred to by name (or index for tuple
+/// variants), so this works for every kind of variant.
+fn variant_pattern(path: TokenStream, variant: &Variant, root: &str) -> TokenStream {
+    let ident = &variant.ident;
+    let bindings = variant.fields.iter().enumerate().map(|(i, f)| {
+        let cfg_attrs = f.attrs.iter().filter(|a| a.path().is_ident(CFG_ATTRIBUTE));
+        let member = match &f.ident {
+            Some(ident) => quote! { #ident },
+            None => {
+                let i = syn::Index::from(i);
+                quote! { #i }
+            }
+        };
+        let binding = format_ident!("{root}_{i}");
+        quote! { #(#cfg_attrs)* #member: #binding }
+    });
+    quote! { #path::#ident { #(#bindings),* } }
 }
 
 struct GenerateTryFromImpl {
     field_assign_acc: TokenStream,
-    field_check_acc: TokenStream,
+    variant_arms: Option<TokenStream>,
+    /// Views leaving out fields cannot be converted to the base.
+    is_partial: bool,
 }
 
 impl GenerateTryFromImpl {
     fn new() -> Self {
         GenerateTryFromImpl {
-            field_check_acc: quote! {},
             field_assign_acc: quote! {},
+            variant_arms: None,
+            is_partial: false,
         }
     }
 
     fn get_implementation(self, derive_input: &DeriveInput, new: &DeriveInput) -> TokenStream {
-        let (impl_generics, ty_generics, where_clause) = derive_input.generics.split_for_impl();
+        if self.is_partial {
+            return quote! {};
+        }
+        // The generated struct has the same generics as the original one, plus the extra bounds
+        let (impl_generics, ty_generics, where_clause) = new.generics.split_for_impl();
         let old_name = &derive_input.ident;
         let new_name = &new.ident;
-        let field_check_acc = self.field_check_acc;
         let field_assign_acc = self.field_assign_acc;
+        let construction = match self.variant_arms {
+            Some(variant_arms) => quote! { match v { #variant_arms } },
+            None => quote! { Self { #field_assign_acc } },
+        };
 
         quote! {
-                impl #impl_generics TryFrom<#new_name #ty_generics > #where_clause for #old_name #ty_generics {
-                    type Error = #new_name #ty_generics;
+                impl #impl_generics TryFrom<#new_name #ty_generics > for #old_name #ty_generics #where_clause {
+                    type Error = optional_struct::MissingFields<#new_name #ty_generics>;
+
+                    fn try_from(v: #new_name #ty_generics) -> Result<Self, Self::Error> {
+                        if !optional_struct::Applicable::can_convert(&v) {
+                            let fields = optional_struct::Applicable::missing_fields(&v);
+                            return Err(optional_struct::MissingFields::new(v, fields));
+                        }
+                        Ok(#construction)
+                    }
+                }
 
-                    fn try_from(v: Self::Error) -> Result<Self, Self::Error> {
-                        #field_check_acc
-                        Ok(Self {
-                            #field_assign_acc
+                impl #impl_generics #new_name #ty_generics #where_clause {
+                    /// Builds the base structure, failing with all the fields that are either not
+                    /// set, or not valid (see `optional_validate`). When some fields are not set,
+                    /// only the ones that are set are validated, except for the validations put on
+                    /// nested fields themselves.
+                    pub fn try_build(self) -> Result<#old_name #ty_generics, optional_struct::ValidationErrors> {
+                        #[allow(unused_imports)]
+                        use optional_struct::__private::{ValidateNested as _, ValidateNestedFallback as _};
+                        // The base may not implement `Validate`, see `GenerateValidateVisitor`
+                        optional_struct::__private::try_build(self, |base: &#old_name #ty_generics| {
+                            let mut errors = optional_struct::__private::Vec::new();
+                            (&&optional_struct::__private::Validated(base)).validate_nested("", &mut errors);
+                            errors
                         })
                     }
                 }
@@ -73,38 +271,26 @@ impl OptionalFieldVisitor for GenerateTryFromImpl {
     fn visit(
         &mut self,
         _global_options: &GlobalOptions,
-        old_field: &mut Field,
+        _old_field: &mut Field,
         _new_field: &mut Field,
         field_options: &FieldOptions,
     ) {
         let ident = &field_options.field_ident;
         let cfg_attr = &field_options.cfg_attribute;
+        let value = field_options.access("v", false);
 
         let is_wrapped = field_options.wrapping_behavior;
         let is_nested = field_options.new_type.is_some();
-        let is_base_opt = is_type_option(&old_field.ty);
-        let (unwrap, check) = match (is_base_opt, is_wrapped, is_nested) {
-            (_, true, false) => (
-                quote! { .unwrap() },
-                quote! { #cfg_attr if v.#ident.is_none() { return Err(v); } },
-            ),
-            (true, true, true) => (
-                quote! { .unwrap().try_into().ok() },
-                quote! { #cfg_attr if let Some(i) = &v.#ident { if !i.can_convert() { return Err(v); } } else { return Err(v); } },
-            ),
-            (false, true, true) => (
-                quote! { .unwrap().try_into().unwrap() },
-                quote! { #cfg_attr if let Some(i) = &v.#ident { if !i.can_convert() { return Err(v); } } else { return Err(v); } },
-            ),
-            (true, false, true) => (
-                quote! { .try_into().ok() },
-                quote! { #cfg_attr if !v.#ident.can_convert() { return Err(v); } },
-            ),
-            (false, false, true) => (
-                quote! { .try_into().unwrap() },
-                quote! { #cfg_attr if !v.#ident.can_convert() { return Err(v); } },
-            ),
-            (_, false, false) => (quote! {}, quote! {}),
+        let is_base_opt = field_options.is_base_opt;
+        // Those cannot fail since `can_convert` was checked beforehand. Going through `ok()`
+        // avoids requiring the nested generated struct (i.e. the error) to implement Debug.
+        let unwrap = match (is_base_opt, is_wrapped, is_nested) {
+            (_, true, false) => quote! { .unwrap() },
+            (true, true, true) => quote! { .unwrap().try_into().ok() },
+            (false, true, true) => quote! { .unwrap().try_into().ok().unwrap() },
+            (true, false, true) => quote! { .try_into().ok() },
+            (false, false, true) => quote! { .try_into().ok().unwrap() },
+            (_, false, false) => quote! {},
         };
 
         let field_assign_acc = &self.field_assign_acc;
@@ -112,14 +298,29 @@ impl OptionalFieldVisitor for GenerateTryFromImpl {
             #field_assign_acc
             #cfg_attr
 
-            #ident: v.#ident #unwrap,
+            #ident: #value #unwrap,
         };
+    }
 
-        let field_check_acc = &self.field_check_acc;
-        self.field_check_acc = quote! {
-            #field_check_acc
-            #check
-        };
+    fn visit_excluded(&mut self, _global_options: &GlobalOptions, _field_options: &FieldOptions) {
+        self.is_partial = true;
+    }
+
+    fn end_variant(
+        &mut self,
+        global_options: &GlobalOptions,
+        _orig_name: &Ident,
+        variant: &Variant,
+    ) {
+        let ident = &variant.ident;
+        let new_name = format_ident!("{}", global_options.new_struct_name);
+        let pattern = variant_pattern(quote! { #new_name }, variant, "v");
+        let field_assign_acc = std::mem::take(&mut self.field_assign_acc);
+        let variant_arms = self.variant_arms.take().unwrap_or_default();
+        self.variant_arms = Some(quote! {
+            #variant_arms
+            #pattern => Self::#ident { #field_assign_acc },
+        });
     }
 }
 
@@ -127,6 +328,18 @@ struct GenerateApplicableImplVisitor {
     acc_concrete: TokenStream,
     acc_opt: TokenStream,
     acc_can_convert: TokenStream,
+    acc_missing_fields: TokenStream,
+    variant_arms: Option<ApplicableVariantArms>,
+    is_partial: bool,
+}
+
+/// The match arms of the `Applicable` methods when generating an enum, one per variant.
+#[derive(Default)]
+struct ApplicableVariantArms {
+    concrete: TokenStream,
+    opt: TokenStream,
+    can_convert: TokenStream,
+    missing_fields: TokenStream,
 }
 
 impl GenerateApplicableImplVisitor {
@@ -135,20 +348,81 @@ impl GenerateApplicableImplVisitor {
             acc_concrete: quote! {},
             acc_opt: quote! {},
             acc_can_convert: quote! {},
+            acc_missing_fields: quote! {},
+            variant_arms: None,
+            is_partial: false,
         }
     }
 
-    fn get_implementation(self, orig: &DeriveInput, new: &DeriveInput) -> TokenStream {
-        let (impl_generics, ty_generics, _) = orig.generics.split_for_impl();
+    fn get_implementation(
+        self,
+        orig: &DeriveInput,
+        new: &DeriveInput,
+        reflection: TokenStream,
+    ) -> TokenStream {
+        let (impl_generics, ty_generics, where_clause) = new.generics.split_for_impl();
         let orig_name = &orig.ident;
         let new_name = &new.ident;
-        let acc_concrete = self.acc_concrete;
-        let acc_opt = self.acc_opt;
-        let acc_can_convert = self.acc_can_convert;
+        let (acc_concrete, acc_opt, acc_can_convert, acc_missing_fields) = match self.variant_arms {
+            // Matching variants are patched field by field, otherwise the whole value is replaced
+            // (if possible, in the case of the base).
+            Some(arms) => {
+                let ApplicableVariantArms {
+                    concrete,
+                    opt,
+                    can_convert,
+                    missing_fields,
+                } = arms;
+                (
+                    quote! {
+                        match (self, t) {
+                            #concrete
+                            #[allow(unreachable_patterns)]
+                            (patch, t) => {
+                                if let Ok(base) = TryFrom::try_from(patch) {
+                                    *t = base;
+                                }
+                            }
+                        }
+                    },
+                    quote! {
+                        match (self, t) {
+                            #opt
+                            #[allow(unreachable_patterns)]
+                            (patch, t) => *t = patch,
+                        }
+                    },
+                    quote! {
+                        match self {
+                            #can_convert
+                        }
+                    },
+                    quote! {
+                        match self {
+                            #missing_fields
+                        }
+                    },
+                )
+            }
+            None => (
+                self.acc_concrete,
+                self.acc_opt,
+                self.acc_can_convert,
+                self.acc_missing_fields,
+            ),
+        };
+        let can_convert = if self.is_partial {
+            quote! { false }
+        } else {
+            quote! {
+                #acc_can_convert
+                true
+            }
+        };
         // TODO: everything was written with "t" as the parameter name, but this a. does not match
         // the trait and b. is not explicit enough. Make this some parameter instead.
         quote! {
-            impl #impl_generics optional_struct::Applicable for #new_name #ty_generics {
+            impl #impl_generics optional_struct::Applicable for #new_name #ty_generics #where_clause {
                 type Base = #orig_name #ty_generics;
 
                 fn apply_to(self, t: &mut Self::Base) {
@@ -160,103 +434,167 @@ impl GenerateApplicableImplVisitor {
                 }
 
                 fn can_convert(&self) -> bool {
-                    #acc_can_convert
-                    true
+                    #can_convert
                 }
+
+                fn missing_fields(&self) -> optional_struct::__private::Vec<optional_struct::__private::String> {
+                    #[allow(unused_mut)]
+                    let mut missing = optional_struct::__private::Vec::new();
+                    #acc_missing_fields
+                    missing
+                }
+
+                #reflection
             }
         }
     }
 
     fn get_incremental_setter_concrete(
-        ident: &TokenStream,
+        src: &TokenStream,
+        dst: &TokenStream,
         is_wrapped: bool,
         is_nested: bool,
         is_base_opt: bool,
     ) -> TokenStream {
         match (is_base_opt, is_wrapped, is_nested) {
             (true, false, true) => quote! {
-                if let Some(existing) = &mut t.#ident {
-                    self.#ident.apply_to(existing);
+                if let Some(existing) = &mut #dst {
+                    #src.apply_to(existing);
                 } else {
-                    t.#ident = self.#ident.try_into().ok();
+                    #dst = #src.try_into().ok();
                 }
             },
             (true, false, false) => quote! {
-                if self.#ident.is_some() {
-                    t.#ident = self.#ident;
+                if #src.is_some() {
+                    #dst = #src;
                 }
             },
-            (false, false, true) => quote! { self.#ident.apply_to(&mut t.#ident); },
-            (false, false, false) => quote! { t.#ident = self.#ident; },
+            (false, false, true) => quote! { #src.apply_to(&mut #dst); },
+            (false, false, false) => quote! { #dst = #src; },
             (true, true, true) => {
-                quote! { if let (Some(inner), Some(target)) = (self.#ident, &mut t.#ident) { inner.apply_to(target); } }
+                quote! { if let (Some(inner), Some(target)) = (#src, &mut #dst) { inner.apply_to(target); } }
             }
             (false, true, true) => {
-                quote! { if let Some(inner) = self.#ident { inner.apply_to(&mut t.#ident); } }
+                quote! { if let Some(inner) = #src { inner.apply_to(&mut #dst); } }
             }
-            (_, true, false) => quote! { if let Some(inner) = self.#ident { t.#ident = inner; } },
+            (_, true, false) => quote! { if let Some(inner) = #src { #dst = inner; } },
         }
     }
     fn get_incremental_setter_opt(
-        ident: &TokenStream,
+        src: &TokenStream,
+        dst: &TokenStream,
         is_wrapped: bool,
         is_nested: bool,
         is_base_opt: bool,
     ) -> TokenStream {
         match (is_base_opt, is_wrapped, is_nested) {
             (_, false, true) => quote! {
-                self.#ident.apply_to_opt(&mut t.#ident);
+                #src.apply_to_opt(&mut #dst);
             },
             (true, false, false) => quote! {
-                if self.#ident.is_some() {
-                    t.#ident = self.#ident;
+                if #src.is_some() {
+                    #dst = #src;
                 }
             },
-            (false, false, false) => quote! { t.#ident = self.#ident; },
+            (false, false, false) => quote! { #dst = #src; },
             (_, true, true) => quote! {
-               match (&mut t.#ident, self.#ident) {
-                   (None, Some(nested)) => t.#ident = Some(nested),
+               match (&mut #dst, #src) {
+                   (None, Some(nested)) => #dst = Some(nested),
                    (Some(existing), Some(nested)) => nested.apply_to_opt(existing),
                    (_, None) => {},
                }
             },
             (_, true, false) => {
-                quote! { if let Some(inner) = self.#ident { t.#ident = Some(inner); } }
+                quote! { if let Some(inner) = #src { #dst = Some(inner); } }
+            }
+        }
+    }
+
+    /// The setter of `apply_to`, merging the value if the field uses `optional_merge`.
+    fn get_setter_concrete(
+        field_options: &FieldOptions,
+        src: &TokenStream,
+        dst: &TokenStream,
+    ) -> TokenStream {
+        let is_wrapped = field_options.wrapping_behavior;
+        let is_nested = field_options.new_type.is_some();
+        let is_base_opt = field_options.is_base_opt;
+        match &field_options.merge_with {
+            Some(merge) => Self::get_merging_setters(src, dst, is_wrapped, is_base_opt, merge).0,
+            None => {
+                Self::get_incremental_setter_concrete(src, dst, is_wrapped, is_nested, is_base_opt)
             }
         }
     }
+
+    /// The setters of `apply_to` and `apply_to_opt` for fields using `optional_merge` (which
+    /// cannot be nested).
+    fn get_merging_setters(
+        src: &TokenStream,
+        dst: &TokenStream,
+        is_wrapped: bool,
+        is_base_opt: bool,
+        merge: &TokenStream,
+    ) -> (TokenStream, TokenStream) {
+        let merge_option = quote! { optional_struct::__private::merge_option };
+        match (is_base_opt, is_wrapped) {
+            (true, true) => (
+                quote! { if let Some(inner) = #src { #merge_option(&mut #dst, inner, #merge); } },
+                quote! {
+                    #merge_option(&mut #dst, #src, |existing, new| #merge_option(existing, new, #merge));
+                },
+            ),
+            (false, true) => (
+                quote! { if let Some(inner) = #src { #merge(&mut #dst, inner); } },
+                quote! { #merge_option(&mut #dst, #src, #merge); },
+            ),
+            (true, false) => (
+                quote! { #merge_option(&mut #dst, #src, #merge); },
+                quote! { #merge_option(&mut #dst, #src, #merge); },
+            ),
+            (false, false) => (
+                quote! { #merge(&mut #dst, #src); },
+                quote! { #merge(&mut #dst, #src); },
+            ),
+        }
+    }
 }
 
 impl OptionalFieldVisitor for GenerateApplicableImplVisitor {
     fn visit(
         &mut self,
         _global_options: &GlobalOptions,
-        old_field: &mut Field,
+        _old_field: &mut Field,
         _new_field: &mut Field,
         field_options: &FieldOptions,
     ) {
-        let ident = &field_options.field_ident;
         let cfg_attr = &field_options.cfg_attribute;
+        let src = field_options.access("self", false);
+        let dst = field_options.access("t", true);
+        let src_ref = field_options.access("self", true);
 
         let is_wrapped = field_options.wrapping_behavior;
         let is_nested = field_options.new_type.is_some();
-        let is_base_opt = is_type_option(&old_field.ty);
-
-        let inc_concrete =
-            Self::get_incremental_setter_concrete(ident, is_wrapped, is_nested, is_base_opt);
-        let inc_opt = Self::get_incremental_setter_opt(
-            ident,
-            is_wrapped,
-            is_nested,
-            is_wrapped || is_base_opt,
-        );
+        let is_base_opt = field_options.is_base_opt;
+
+        let inc_concrete = Self::get_setter_concrete(field_options, &src, &dst);
+        let inc_opt = match &field_options.merge_with {
+            Some(merge) => Self::get_merging_setters(&src, &dst, is_wrapped, is_base_opt, merge).1,
+            None => Self::get_incremental_setter_opt(
+                &src,
+                &dst,
+                is_wrapped,
+                is_nested,
+                is_wrapped || is_base_opt,
+            ),
+        };
 
         let acc_concrete = &self.acc_concrete;
         self.acc_concrete = quote! {
             #acc_concrete
 
             #cfg_attr
-            #inc_concrete
+            { #inc_concrete }
         };
 
         let acc_opt = &self.acc_opt;
@@ -264,18 +602,43 @@ impl OptionalFieldVisitor for GenerateApplicableImplVisitor {
             #acc_opt
 
             #cfg_attr
-            #inc_opt
+            { #inc_opt }
+        };
+
+        let name = field_name(field_options);
+        let inc_missing_fields = match (is_base_opt, is_wrapped, is_nested) {
+            (_, true, false) => quote! {
+                if #src_ref.is_none() {
+                    optional_struct::__private::push_missing_field(&mut missing, #name);
+                }
+            },
+            (_, true, true) => quote! {
+                match &#src_ref {
+                    Some(i) => optional_struct::__private::push_nested_missing_fields(&mut missing, #name, i),
+                    None => optional_struct::__private::push_missing_field(&mut missing, #name),
+                }
+            },
+            (_, false, true) => quote! {
+                optional_struct::__private::push_nested_missing_fields(&mut missing, #name, &#src_ref);
+            },
+            (_, false, false) => quote! {},
+        };
+        let acc_missing_fields = &self.acc_missing_fields;
+        self.acc_missing_fields = quote! {
+            #acc_missing_fields
+            #cfg_attr
+            { #inc_missing_fields }
         };
 
         let inc_can_convert = match (is_base_opt, is_wrapped, is_nested) {
             (_, true, false) => quote! {
-                if self.#ident.is_none() {
+                if #src_ref.is_none() {
                     return false;
                 }
             },
             (_, true, true) => {
                 quote! {
-                    if let Some(i) = &self.#ident {
+                    if let Some(i) = &#src_ref {
                         if !i.can_convert() {
                             return false;
                         }
@@ -285,11 +648,12 @@ impl OptionalFieldVisitor for GenerateApplicableImplVisitor {
                 }
             }
             (_, false, true) => quote! {
-                if !self.#ident.can_convert() {
+                if !#src_ref.can_convert() {
                     return false;
                 }
             },
-            (_, false, false) => quote! {},
+            // Nothing to check, and a lone `cfg` attribute would apply to what follows
+            (_, false, false) => return,
         };
         let acc_can_convert = &self.acc_can_convert;
         self.acc_can_convert = quote! {
@@ -298,284 +662,2240 @@ impl OptionalFieldVisitor for GenerateApplicableImplVisitor {
             #inc_can_convert
         };
     }
-}
 
-struct SetNewFieldVisibilityVisitor;
+    fn visit_excluded(&mut self, _global_options: &GlobalOptions, field_options: &FieldOptions) {
+        self.is_partial = true;
 
-impl OptionalFieldVisitor for SetNewFieldVisibilityVisitor {
-    fn visit(
-        &mut self,
-        global_options: &GlobalOptions,
-        _old_field: &mut Field,
-        new_field: &mut Field,
-        _field_options: &FieldOptions,
-    ) {
-        if global_options.make_fields_public {
-            new_field.vis = Visibility::Public(syn::token::Pub(new_field.vis.span()))
-        }
+        // Fields left out of the view can never be set
+        let cfg_attr = &field_options.cfg_attribute;
+        let name = field_name(field_options);
+        let acc_missing_fields = &self.acc_missing_fields;
+        self.acc_missing_fields = quote! {
+            #acc_missing_fields
+            #cfg_attr
+            { optional_struct::__private::push_missing_field(&mut missing, #name); }
+        };
     }
-}
-
-struct SetNewFieldTypeVisitor;
 
-impl OptionalFieldVisitor for SetNewFieldTypeVisitor {
-    fn visit(
+    fn end_variant(
         &mut self,
         _global_options: &GlobalOptions,
-        old_field: &mut Field,
-        new_field: &mut Field,
-        field_options: &FieldOptions,
+        orig_name: &Ident,
+        variant: &Variant,
     ) {
-        let mut new_type = if let Some(t) = &field_options.new_type {
-            quote! {#t}
-        } else {
-            let t = &old_field.ty;
-            quote! {#t}
+        let self_pattern = variant_pattern(quote! { Self }, variant, "self");
+        let base_pattern = variant_pattern(quote! { #orig_name }, variant, "t");
+        let target_pattern = variant_pattern(quote! { Self }, variant, "t");
+        let concrete = std::mem::take(&mut self.acc_concrete);
+        let opt = std::mem::take(&mut self.acc_opt);
+        let can_convert = std::mem::take(&mut self.acc_can_convert);
+        let missing_fields = std::mem::take(&mut self.acc_missing_fields);
+
+        let arms = self.variant_arms.get_or_insert_with(Default::default);
+        let acc_concrete = &arms.concrete;
+        arms.concrete = quote! {
+            #acc_concrete
+            #[allow(unused_variables)]
+            (#self_pattern, #base_pattern) => { #concrete }
         };
-
-        if field_options.wrapping_behavior {
-            new_type = quote! {Option<#new_type>};
+        let acc_opt = &arms.opt;
+        arms.opt = quote! {
+            #acc_opt
+            #[allow(unused_variables)]
+            (#self_pattern, #target_pattern) => { #opt }
+        };
+        let acc_can_convert = &arms.can_convert;
+        arms.can_convert = quote! {
+            #acc_can_convert
+            #[allow(unused_variables)]
+            #self_pattern => { #can_convert }
+        };
+        let acc_missing_fields = &arms.missing_fields;
+        arms.missing_fields = quote! {
+            #acc_missing_fields
+            #[allow(unused_variables)]
+            #self_pattern => { #missing_fields }
         };
-        new_field.ty = Type::Verbatim(new_type);
     }
 }
 
-struct AddSerdeSkipAttribute;
+/// Generates the `FIELD_NAMES` and `visit_with_prefix` items of the `Applicable` implementation.
+struct GenerateReflectionVisitor {
+    /// The names of the fields, along with their `cfg` attributes. Fields of enum variants sharing
+    /// the same name (and `cfg` attributes) are only listed once.
+    field_names: Vec<(String, TokenStream)>,
+    acc_visit: TokenStream,
+    variant_arms: Option<TokenStream>,
+    /// The generic parameters of the struct, whose fields referring to them are never `Any`.
+    generic_params: Vec<String>,
+}
 
-impl OptionalFieldVisitor for AddSerdeSkipAttribute {
-    fn visit(
-        &mut self,
-        _global_options: &GlobalOptions,
-        _old_field: &mut Field,
-        new_field: &mut Field,
-        field_options: &FieldOptions,
-    ) {
-        if !field_options.serde_skip {
-            return;
+impl GenerateReflectionVisitor {
+    fn new(generics: &syn::Generics) -> Self {
+        GenerateReflectionVisitor {
+            field_names: Vec::new(),
+            acc_visit: quote! {},
+            variant_arms: None,
+            generic_params: generics
+                .type_params()
+                .map(|p| p.ident.to_string())
+                .collect(),
         }
+    }
 
-        let attribute: Attribute =
-            parse_quote! { #[serde(skip_serializing_if = "Option::is_none")] };
-        new_field.attrs.push(attribute);
+    fn get_implementation(self) -> TokenStream {
+        let field_names = self
+            .field_names
+            .iter()
+            .map(|(name, cfg_attr)| quote! { #cfg_attr #name });
+        let acc_visit = match self.variant_arms {
+            Some(arms) => quote! { match self { #arms } },
+            None => self.acc_visit,
+        };
+        quote! {
+            const FIELD_NAMES: &'static [&'static str] = &[#(#field_names),*];
+
+            #[allow(unused_variables)]
+            fn visit_with_prefix(&self, prefix: &str, visitor: &mut dyn optional_struct::PatchVisitor) {
+                #[allow(unused_imports)]
+                use optional_struct::__private::{
+                    ReflectAny as _, ReflectAnyFallback as _, ReflectDebug as _,
+                    ReflectDebugFallback as _,
+                };
+                #acc_visit
+            }
+        }
     }
-}
 
-// https://github.com/rust-lang/rust/issues/65823 :(
-struct RemoveHelperAttributesVisitor;
+    /// Whether the type may not be `'static`, in which case it cannot be turned into a `dyn Any`
+    /// (autoref specialization does not work with lifetimes).
+    fn may_borrow(&self, tokens: TokenStream) -> bool {
+        tokens.into_iter().any(|tt| match tt {
+            proc_macro2::TokenTree::Punct(p) => p.as_char() == '\'',
+            proc_macro2::TokenTree::Ident(i) => self.generic_params.iter().any(|p| i == p),
+            proc_macro2::TokenTree::Group(g) => self.may_borrow(g.stream()),
+            proc_macro2::TokenTree::Literal(_) => false,
+        })
+    }
+}
 
-impl OptionalFieldVisitor for RemoveHelperAttributesVisitor {
+impl OptionalFieldVisitor for GenerateReflectionVisitor {
     fn visit(
         &mut self,
         _global_options: &GlobalOptions,
-        old_field: &mut Field,
+        _old_field: &mut Field,
         new_field: &mut Field,
-        _field_options: &FieldOptions,
+        field_options: &FieldOptions,
     ) {
-        let indexes_to_remove = old_field
-            .attrs
+        let cfg_attr = &field_options.cfg_attribute;
+        let src_ref = field_options.access("self", true);
+        let name = field_name(field_options);
+        let is_listed = self
+            .field_names
             .iter()
-            .enumerate()
-            .filter_map(|(i, a)| {
-                if a.path().is_ident(RENAME_ATTRIBUTE)
-                    || a.path().is_ident(SKIP_WRAP_ATTRIBUTE)
-                    || a.path().is_ident(WRAP_ATTRIBUTE)
-                    || a.path().is_ident(SERDE_SKIP_SERIALIZING_NONE)
-                {
-                    Some(i)
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
-
-        // Don't forget to reverse so the indices are removed without being shifted!
-        for i in indexes_to_remove.into_iter().rev() {
-            old_field.attrs.swap_remove(i);
-            new_field.attrs.swap_remove(i);
+            .any(|(n, c)| *n == name && c.to_string() == cfg_attr.to_string());
+        if !is_listed {
+            self.field_names.push((name.clone(), cfg_attr.clone()));
         }
-    }
-}
-
-fn borrow_fields(derive_input: &mut DeriveInput) -> &mut Punctuated<Field, Comma> {
-    let data_struct = match &mut derive_input.data {
-        Data::Struct(data_struct) => data_struct,
-        _ => panic!("OptionalStruct only works for structs :)"),
-    };
-
-    match &mut data_struct.fields {
-        Fields::Unnamed(f) => &mut f.unnamed,
-        Fields::Named(f) => &mut f.named,
-        Fields::Unit => unreachable!("A struct cannot have simply a unit field?"),
-    }
-}
 
-fn visit_fields(
-    visitors: &mut [&mut dyn OptionalFieldVisitor],
-    global_options: &GlobalOptions,
-    derive_input: &DeriveInput,
-) -> (DeriveInput, DeriveInput) {
-    let mut new = derive_input.clone();
-    let mut orig = derive_input.clone();
-    let old_fields = borrow_fields(&mut orig);
-    let new_fields = borrow_fields(&mut new);
+        let is_wrapped = field_options.wrapping_behavior;
+        let is_nested = field_options.new_type.is_some();
+        let is_base_opt = field_options.is_base_opt;
 
-    for (struct_index, (old_field, new_field)) in
-        old_fields.iter_mut().zip(new_fields.iter_mut()).enumerate()
-    {
-        let mut overriden_wrapping = false;
-        let mut wrapping_behavior =
-            !is_type_option(&old_field.ty) && global_options.default_wrapping_behavior;
-        let mut cfg_attribute = None;
-        let mut new_type = None;
-        let mut serde_skip = false;
-        old_field.attrs
-            .iter()
-            .for_each(|a| {
-                if a.path().is_ident(RENAME_ATTRIBUTE) {
-                    let args = a
-                        .parse_args()
-                        .unwrap_or_else(|_| panic!("'{RENAME_ATTRIBUTE}' attribute expects one and only one argument (the new type to use)"));
-                    new_type = Some(args);
-                    if !overriden_wrapping {
-                        wrapping_behavior = false;
-                    }
-                } else if a.path().is_ident(SKIP_WRAP_ATTRIBUTE) {
-                    wrapping_behavior = false;
-                    overriden_wrapping = true;
-                } else if a.path().is_ident(WRAP_ATTRIBUTE) {
-                    wrapping_behavior = true;
-                    overriden_wrapping = true;
-                } else if a.path().is_ident(SERDE_SKIP_SERIALIZING_NONE) {
-                    serde_skip = true;
-                } else if a.path().is_ident(CFG_ATTRIBUTE) {
-                    cfg_attribute = Some(a.clone());
-                }
-            });
-        let field_ident = if let Some(ident) = &old_field.ident {
-            quote! {#ident}
+        let ty = &new_field.ty;
+        let any = if self.may_borrow(quote! { #ty }) {
+            quote! { None }
         } else {
-            let i = syn::Index::from(struct_index);
-            quote! {#i}
+            quote! { (&&optional_struct::__private::Reflected(&#src_ref)).reflect_any() }
         };
-        let field_options = FieldOptions {
-            wrapping_behavior,
-            cfg_attribute,
-            new_type,
-            field_ident,
-            serde_skip,
+        let is_set = if is_wrapped || is_base_opt {
+            quote! { #src_ref.is_some() }
+        } else {
+            quote! { true }
         };
-        for v in &mut *visitors {
-            v.visit(global_options, old_field, new_field, &field_options);
-        }
-    }
-    (orig, new)
-}
-
-fn get_derive_macros(new: &DeriveInput, extra_derive: &[String]) -> TokenStream {
-    let mut extra_derive = extra_derive.iter().collect::<HashSet<_>>();
-    for attributes in &new.attrs {
-        let _ = attributes.parse_nested_meta(|derived_trait| {
-            let derived_trait = derived_trait.path;
-            let full_path = quote! { #derived_trait };
-            extra_derive.remove(&full_path.to_string());
-            Ok(())
-        });
-    }
-
-    let mut acc = quote! {};
-    for left_trait_to_derive in extra_derive {
-        let left_trait_to_derive = format_ident!("{left_trait_to_derive}");
-        acc = quote! { # left_trait_to_derive, # acc};
-    }
-
-    quote! { #[derive(#acc)] }
-}
-
-struct ParsedMacroParameters {
-    new_struct_name: Option<String>,
-    default_wrapping: bool,
-}
-
-impl Parse for ParsedMacroParameters {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let mut out = ParsedMacroParameters {
-            new_struct_name: None,
-            default_wrapping: true,
+        let visit_field = quote! {
+            visitor.visit_field(&optional_struct::PatchField {
+                name: #name,
+                path: &optional_struct::__private::field_path(prefix, #name),
+                is_set: #is_set,
+                debug: (&&optional_struct::__private::Reflected(&#src_ref)).reflect_debug(),
+                any: #any,
+            });
         };
-
-        if let Ok(struct_name) = Ident::parse(input) {
-            out.new_struct_name = Some(struct_name.to_string());
-        } else {
-            return Ok(out);
+        let visit_nested = |nested: TokenStream| {
+            quote! {
+                optional_struct::Applicable::visit_with_prefix(
+                    #nested,
+                    &optional_struct::__private::nested_prefix(prefix, #name),
+                    visitor,
+                );
+            }
         };
-
-        if input.parse::<Token![,]>().is_err() {
-            return Ok(out);
+        let inc_visit = match (is_wrapped, is_nested) {
+            (_, false) => visit_field,
+            (false, true) => visit_nested(quote! { &#src_ref }),
+            (true, true) => {
+                let visit_nested = visit_nested(quote! { i });
+                quote! {
+                    match &#src_ref {
+                        Some(i) => { #visit_nested }
+                        None => { #visit_field }
+                    }
+                }
+            }
         };
 
-        if let Ok(wrapping) = syn::LitBool::parse(input) {
-            out.default_wrapping = wrapping.value;
-        } else {
-            return Ok(out);
+        let acc_visit = &self.acc_visit;
+        self.acc_visit = quote! {
+            #acc_visit
+            #cfg_attr
+            { #inc_visit }
         };
+    }
 
-        Ok(out)
+    fn end_variant(
+        &mut self,
+        _global_options: &GlobalOptions,
+        _orig_name: &Ident,
+        variant: &Variant,
+    ) {
+        let self_pattern = variant_pattern(quote! { Self }, variant, "self");
+        let visit = std::mem::take(&mut self.acc_visit);
+        let arms = self.variant_arms.take().unwrap_or_default();
+        self.variant_arms = Some(quote! {
+            #arms
+            #[allow(unused_variables)]
+            #self_pattern => { #visit }
+        });
     }
 }
 
-// TODO this breaks for e.g. yolo::my::Option
-fn is_path_option(p: &Path) -> bool {
-    p.segments
-        .last()
-        .map(|ps| ps.ident == "Option")
-        .unwrap_or(false)
+/// Generates `From<Base>` (every field set) and `From<&Base>` (cloning the fields) for the generated
+/// struct.
+struct GenerateFromBaseVisitor {
+    owned_inits: TokenStream,
+    ref_inits: TokenStream,
+    owned_bounds: Vec<WherePredicate>,
+    ref_bounds: Vec<WherePredicate>,
+    variant_arms: Option<(TokenStream, TokenStream)>,
 }
 
-fn is_type_option(t: &Type) -> bool {
-    macro_rules! wtf {
-        ($reason : tt) => {
-            panic!(
-                "Using OptionalStruct for a struct containing a {} is dubious...",
-                $reason
-            )
-        };
+impl GenerateFromBaseVisitor {
+    fn new() -> Self {
+        GenerateFromBaseVisitor {
+            owned_inits: quote! {},
+            ref_inits: quote! {},
+            owned_bounds: Vec::new(),
+            ref_bounds: Vec::new(),
+            variant_arms: None,
+        }
     }
 
-    match &t {
-        // real work
-        Type::Path(type_path) => is_path_option(&type_path.path),
-        Type::Array(_) | Type::Tuple(_) => false,
-        Type::Paren(type_paren) => is_type_option(&type_paren.elem),
-
-        // No clue what to do with those
-        Type::ImplTrait(_) | Type::TraitObject(_) => {
-            panic!("Might already be an option I have no way to tell :/")
+    /// The conversion of `value` (of the type of the field in the base) to the type of the field
+    /// in the generated struct.
+    fn get_conversion(field_options: &FieldOptions, value: TokenStream) -> TokenStream {
+        let is_wrapped = field_options.wrapping_behavior;
+        let is_nested = field_options.new_type.is_some();
+        let is_base_opt = field_options.is_base_opt;
+        match (is_base_opt, is_wrapped, is_nested) {
+            (_, true, false) => quote! { Some(#value) },
+            (_, false, false) => value,
+            (false, false, true) => quote! { From::from(#value) },
+            (false, true, true) => quote! { Some(From::from(#value)) },
+            (true, false, true) => quote! {
+                match #value {
+                    Some(nested) => From::from(nested),
+                    None => Default::default(),
+                }
+            },
+            (true, true, true) => quote! { #value.map(From::from) },
+        }
+    }
+
+    fn get_implementation(
+        self,
+        global_options: &GlobalOptions,
+        orig: &DeriveInput,
+        new: &DeriveInput,
+    ) -> TokenStream {
+        let (_, ty_generics, _) = new.generics.split_for_impl();
+        let orig_name = &orig.ident;
+        let new_name = &new.ident;
+        let (owned_construction, ref_construction) = match self.variant_arms {
+            Some((owned_arms, ref_arms)) => (
+                quote! { match v { #owned_arms } },
+                quote! { match v { #ref_arms } },
+            ),
+            None => {
+                let (owned_inits, ref_inits) = (self.owned_inits, self.ref_inits);
+                (
+                    quote! { Self { #owned_inits } },
+                    quote! { Self { #ref_inits } },
+                )
+            }
+        };
+
+        let mut owned_generics = new.generics.clone();
+        owned_generics
+            .make_where_clause()
+            .predicates
+            .extend(self.owned_bounds);
+        let (impl_generics, _, where_clause) = owned_generics.split_for_impl();
+        let owned_impl = quote! {
+            impl #impl_generics From<#orig_name #ty_generics> for #new_name #ty_generics #where_clause {
+                fn from(v: #orig_name #ty_generics) -> Self {
+                    #owned_construction
+                }
+            }
+        };
+
+        // The fields are cloned, which the generated struct deriving Clone most likely allows
+        if !is_derived(new, &global_options.extra_derive, "core::clone::Clone") {
+            return owned_impl;
+        }
+        let mut ref_generics = new.generics.clone();
+        ref_generics
+            .params
+            .insert(0, parse_quote! { '__optional_ref });
+        ref_generics
+            .make_where_clause()
+            .predicates
+            .extend(self.ref_bounds);
+        let (impl_generics, _, where_clause) = ref_generics.split_for_impl();
+        quote! {
+            #owned_impl
+
+            impl #impl_generics From<&'__optional_ref #orig_name #ty_generics> for #new_name #ty_generics #where_clause {
+                fn from(v: &'__optional_ref #orig_name #ty_generics) -> Self {
+                    #ref_construction
+                }
+            }
+        }
+    }
+}
+
+impl OptionalFieldVisitor for GenerateFromBaseVisitor {
+    fn visit(
+        &mut self,
+        _global_options: &GlobalOptions,
+        old_field: &mut Field,
+        _new_field: &mut Field,
+        field_options: &FieldOptions,
+    ) {
+        let member = &field_options.field_ident;
+        let cfg_attr = &field_options.cfg_attribute;
+        let owned_value = Self::get_conversion(field_options, field_options.access("v", false));
+        let field = field_options.access("v", true);
+        let ref_value = match &field_options.new_type {
+            Some(_) if field_options.is_base_opt => {
+                Self::get_conversion(field_options, quote! { #field.as_ref() })
+            }
+            Some(_) => Self::get_conversion(field_options, quote! { &#field }),
+            None => Self::get_conversion(field_options, quote! { #field.clone() }),
+        };
+
+        // The type of a field behind a `cfg` may not exist at all
+        if cfg_attr.is_empty() {
+            let ty = &old_field.ty;
+            let base = if field_options.is_base_opt {
+                option_inner_type(ty)
+            } else {
+                Some(ty)
+            };
+            match (&field_options.new_type, base) {
+                (None, _) => self.ref_bounds.push(parse_quote! { #ty: Clone }),
+                (Some(new_type), Some(base)) => {
+                    self.owned_bounds
+                        .push(parse_quote! { #new_type: From<#base> });
+                    self.ref_bounds
+                        .push(parse_quote! { #new_type: From<&'__optional_ref #base> });
+                }
+                (Some(_), None) => {}
+            }
+        }
+
+        let owned_inits = &self.owned_inits;
+        self.owned_inits = quote! {
+            #owned_inits
+            #cfg_attr
+            #member: #owned_value,
+        };
+        let ref_inits = &self.ref_inits;
+        self.ref_inits = quote! {
+            #ref_inits
+            #cfg_attr
+            #member: #ref_value,
+        };
+    }
+
+    fn end_variant(
+        &mut self,
+        _global_options: &GlobalOptions,
+        orig_name: &Ident,
+        variant: &Variant,
+    ) {
+        let ident = &variant.ident;
+        let pattern = variant_pattern(quote! { #orig_name }, variant, "v");
+        let owned_inits = std::mem::take(&mut self.owned_inits);
+        let ref_inits = std::mem::take(&mut self.ref_inits);
+        let (owned_arms, ref_arms) = self.variant_arms.take().unwrap_or_default();
+        self.variant_arms = Some((
+            quote! {
+                #owned_arms
+                #pattern => Self::#ident { #owned_inits },
+            },
+            quote! {
+                #ref_arms
+                #pattern => Self::#ident { #ref_inits },
+            },
+        ));
+    }
+}
+
+/// Generates the `Diff` implementation, setting the fields that differ between two bases.
+struct GenerateDiffVisitor {
+    field_inits: TokenStream,
+    bounds: Vec<WherePredicate>,
+    is_partial: bool,
+    has_merged_fields: bool,
+}
+
+impl GenerateDiffVisitor {
+    fn new() -> Self {
+        GenerateDiffVisitor {
+            field_inits: quote! {},
+            bounds: Vec::new(),
+            is_partial: false,
+            has_merged_fields: false,
+        }
+    }
+
+    fn get_implementation(
+        self,
+        global_options: &GlobalOptions,
+        orig: &DeriveInput,
+        new: &DeriveInput,
+    ) -> TokenStream {
+        // There is no sensible diff between different variants, the fields left out of a view
+        // could not be patched, and merged fields are not simply replaced by the patch.
+        let derives_requirements =
+            is_derived(new, &global_options.extra_derive, "core::clone::Clone")
+                && is_derived(new, &global_options.extra_derive, "core::cmp::PartialEq");
+        if self.is_partial
+            || self.has_merged_fields
+            || !derives_requirements
+            || !matches!(orig.data, Data::Struct(_))
+        {
+            return quote! {};
+        }
+
+        let mut generics = new.generics.clone();
+        generics.make_where_clause().predicates.extend(self.bounds);
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        let new_name = &new.ident;
+        let field_inits = self.field_inits;
+        quote! {
+            impl #impl_generics optional_struct::Diff for #new_name #ty_generics #where_clause {
+                fn diff(old: &Self::Base, new: &Self::Base) -> Self {
+                    Self { #field_inits }
+                }
+            }
+        }
+    }
+}
+
+impl OptionalFieldVisitor for GenerateDiffVisitor {
+    fn visit(
+        &mut self,
+        _global_options: &GlobalOptions,
+        old_field: &mut Field,
+        _new_field: &mut Field,
+        field_options: &FieldOptions,
+    ) {
+        let member = &field_options.field_ident;
+        let cfg_attr = &field_options.cfg_attribute;
+        let old = field_options.access("old", false);
+        let new = field_options.access("new", false);
+        self.has_merged_fields |= field_options.merge_with.is_some();
+
+        let is_wrapped = field_options.wrapping_behavior;
+        let is_base_opt = field_options.is_base_opt;
+        let (value, bound) = match &field_options.new_type {
+            None => {
+                let value = match (is_base_opt, is_wrapped) {
+                    (_, true) => quote! { if #old != #new { Some(#new.clone()) } else { None } },
+                    (true, false) => quote! { if #old != #new { #new.clone() } else { None } },
+                    (false, false) => quote! { #new.clone() },
+                };
+                let ty = &old_field.ty;
+                (value, vec![parse_quote! { #ty: Clone + PartialEq }])
+            }
+            Some(new_type) => {
+                let diff = |old: TokenStream, new: TokenStream| {
+                    quote! { <#new_type as optional_struct::Diff>::diff(#old, #new) }
+                };
+                let non_empty_diff = |old: TokenStream, new: TokenStream| {
+                    let diff = diff(old, new);
+                    quote! {{
+                        let diff = #diff;
+                        if optional_struct::Applicable::is_empty(&diff) { None } else { Some(diff) }
+                    }}
+                };
+                let value = match (is_base_opt, is_wrapped) {
+                    (false, false) => diff(quote! { &#old }, quote! { &#new }),
+                    (false, true) => non_empty_diff(quote! { &#old }, quote! { &#new }),
+                    // A patch cannot reset the base to `None`
+                    (true, false) => {
+                        let diff = diff(quote! { old }, quote! { new });
+                        quote! {
+                            match (&#old, &#new) {
+                                (Some(old), Some(new)) => #diff,
+                                (None, Some(new)) => From::from(new),
+                                _ => Default::default(),
+                            }
+                        }
+                    }
+                    (true, true) => {
+                        let diff = non_empty_diff(quote! { old }, quote! { new });
+                        quote! {
+                            match (&#old, &#new) {
+                                (Some(old), Some(new)) => #diff,
+                                _ => None,
+                            }
+                        }
+                    }
+                };
+                // Without the base, a bound on a generic nested struct would hide its `Base`
+                let base = if is_base_opt {
+                    option_inner_type(&old_field.ty)
+                } else {
+                    Some(&old_field.ty)
+                };
+                let mut bounds = Vec::new();
+                if let Some(base) = base {
+                    if is_base_opt && !is_wrapped {
+                        bounds.push(parse_quote! {
+                            #new_type: optional_struct::Diff
+                                + optional_struct::Applicable<Base = #base>
+                                + Default
+                                + for<'__optional_ref> From<&'__optional_ref #base>
+                        });
+                    } else {
+                        bounds.push(parse_quote! {
+                            #new_type: optional_struct::Diff + optional_struct::Applicable<Base = #base>
+                        });
+                    }
+                }
+                (value, bounds)
+            }
+        };
+        // The type of a field behind a `cfg` may not exist at all
+        if cfg_attr.is_empty() {
+            self.bounds.extend(bound);
+        }
+
+        let field_inits = &self.field_inits;
+        self.field_inits = quote! {
+            #field_inits
+            #cfg_attr
+            #member: #value,
+        };
+    }
+
+    fn visit_excluded(&mut self, _global_options: &GlobalOptions, _field_options: &FieldOptions) {
+        self.is_partial = true;
+    }
+}
+
+/// Generates `build_with_defaults`, where the fields left unset are taken from (in that order)
+/// `optional_default`, the struct-level default base, or `Default::default()`.
+struct GenerateBuildWithDefaults {
+    field_inits: TokenStream,
+    has_defaults: bool,
+}
+
+impl GenerateBuildWithDefaults {
+    fn new() -> Self {
+        GenerateBuildWithDefaults {
+            field_inits: quote! {},
+            has_defaults: false,
+        }
+    }
+
+    fn get_default(global_options: &GlobalOptions, field_options: &FieldOptions) -> TokenStream {
+        let member = &field_options.field_ident;
+        match (&field_options.default_value, &global_options.default_base) {
+            (Some(default_value), _) => default_value.clone(),
+            (None, Some(_)) => quote! { base.#member },
+            (None, None) => quote! { Default::default() },
+        }
+    }
+
+    fn get_implementation(
+        self,
+        global_options: &GlobalOptions,
+        orig: &DeriveInput,
+        new: &DeriveInput,
+    ) -> TokenStream {
+        // Otherwise every field would be required to implement Default
+        if !self.has_defaults && global_options.default_base.is_none() {
+            return quote! {};
+        }
+
+        let (impl_generics, ty_generics, where_clause) = new.generics.split_for_impl();
+        let orig_name = &orig.ident;
+        let new_name = &new.ident;
+        let field_inits = self.field_inits;
+        let base = global_options.default_base.as_ref().map(|default_base| {
+            quote! {
+                #[allow(unused_variables)]
+                let base: #orig_name #ty_generics = #default_base();
+            }
+        });
+        quote! {
+            impl #impl_generics #new_name #ty_generics #where_clause {
+                /// Builds the base structure, taking the fields that are not set from their
+                /// defaults.
+                pub fn build_with_defaults(self) -> #orig_name #ty_generics {
+                    #base
+                    #orig_name { #field_inits }
+                }
+            }
+        }
+    }
+}
+
+impl OptionalFieldVisitor for GenerateBuildWithDefaults {
+    fn visit(
+        &mut self,
+        global_options: &GlobalOptions,
+        _old_field: &mut Field,
+        _new_field: &mut Field,
+        field_options: &FieldOptions,
+    ) {
+        let member = &field_options.field_ident;
+        let cfg_attr = &field_options.cfg_attribute;
+        let src = field_options.access("self", false);
+        let default = Self::get_default(global_options, field_options);
+        self.has_defaults |= field_options.default_value.is_some();
+
+        let is_wrapped = field_options.wrapping_behavior;
+        let is_nested = field_options.new_type.is_some();
+        let is_base_opt = field_options.is_base_opt;
+        // The default is patched the same way `apply_to` would patch the base, except for fields
+        // that are always set (and not merged).
+        let is_merged = field_options.merge_with.is_some();
+        let value = if !is_wrapped && !is_nested && !is_base_opt && !is_merged {
+            src
+        } else {
+            let setter = GenerateApplicableImplVisitor::get_setter_concrete(
+                field_options,
+                &src,
+                &quote! { value },
+            );
+            quote! {
+                {
+                    let mut value = #default;
+                    #setter
+                    value
+                }
+            }
+        };
+
+        let field_inits = &self.field_inits;
+        self.field_inits = quote! {
+            #field_inits
+            #cfg_attr
+            #member: #value,
+        };
+    }
+
+    fn visit_excluded(&mut self, global_options: &GlobalOptions, field_options: &FieldOptions) {
+        let member = &field_options.field_ident;
+        let cfg_attr = &field_options.cfg_attribute;
+        let default = Self::get_default(global_options, field_options);
+        self.has_defaults |= field_options.default_value.is_some();
+
+        let field_inits = &self.field_inits;
+        self.field_inits = quote! {
+            #field_inits
+            #cfg_attr
+            #member: #default,
+        };
+    }
+}
+
+/// Generates the `Validate` impls of the base and of the generated struct, running the checks of
+/// `optional_validate` (on the fields that are set, for the latter) and recursing into nested
+/// structures.
+struct GenerateValidateVisitor {
+    checks: TokenStream,
+    patch_checks: TokenStream,
+}
+
+impl GenerateValidateVisitor {
+    fn new() -> Self {
+        GenerateValidateVisitor {
+            checks: quote! {},
+            patch_checks: quote! {},
+        }
+    }
+
+    fn get_implementation(
+        self,
+        orig: &DeriveInput,
+        new: &DeriveInput,
+        is_main_view: bool,
+    ) -> TokenStream {
+        // The impl on the base can only be generated once, whatever the number of views. It is
+        // left to the user (e.g. for checks across fields) when there is nothing to check.
+        let base_impl = if is_main_view && !self.checks.is_empty() {
+            Self::get_validate_impl(orig, self.checks)
+        } else {
+            quote! {}
+        };
+        let patch_impl = Self::get_validate_impl(new, self.patch_checks);
+        quote! {
+            #base_impl
+            #patch_impl
+        }
+    }
+
+    fn get_validate_impl(input: &DeriveInput, checks: TokenStream) -> TokenStream {
+        let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+        let name = &input.ident;
+        let checks = if checks.is_empty() {
+            quote! { let _ = (prefix, errors); }
+        } else {
+            checks
+        };
+        quote! {
+            impl #impl_generics optional_struct::Validate for #name #ty_generics #where_clause {
+                fn validate_with_prefix(
+                    &self,
+                    prefix: &str,
+                    errors: &mut optional_struct::__private::Vec<optional_struct::ValidationError>,
+                ) {
+                    #[allow(unused_imports)]
+                    use optional_struct::__private::{ValidateNested as _, ValidateNestedFallback as _};
+                    #checks
+                }
+            }
+        }
+    }
+
+    fn get_calls(field_options: &FieldOptions) -> impl Iterator<Item = TokenStream> + '_ {
+        let name = field_name(field_options);
+        field_options.validations.iter().map(move |v| {
+            let Validation {
+                function,
+                extra_args,
+            } = v;
+            quote! {
+                optional_struct::__private::push_validation_error(
+                    errors,
+                    prefix,
+                    #name,
+                    #function(value #extra_args),
+                );
+            }
+        })
+    }
+
+    fn get_nested(field_options: &FieldOptions) -> Option<TokenStream> {
+        let value = field_options.access("self", false);
+        let name = field_name(field_options);
+        field_options.new_type.as_ref().map(|_| {
+            quote! {
+                (&&optional_struct::__private::Validated(&#value)).validate_nested(
+                    &optional_struct::__private::nested_prefix(prefix, #name),
+                    errors,
+                );
+            }
+        })
+    }
+
+    fn add_checks(
+        checks: &mut TokenStream,
+        field_options: &FieldOptions,
+        field_checks: TokenStream,
+    ) {
+        if field_checks.is_empty() {
+            return;
+        }
+        let cfg_attr = &field_options.cfg_attribute;
+        *checks = quote! {
+            #checks
+            #cfg_attr
+            { #field_checks }
+        };
+    }
+
+    fn add_base_checks(&mut self, field_options: &FieldOptions) {
+        // Validations cannot be put on the fields of variants, which are not recursed into either
+        if field_options.in_variant {
+            return;
+        }
+        let value = field_options.access("self", false);
+        let calls = Self::get_calls(field_options);
+        let validations = if field_options.validations.is_empty() {
+            quote! {}
+        } else if field_options.is_base_opt && field_options.new_type.is_none() {
+            quote! { if let Some(value) = &#value { #(#calls)* } }
+        } else {
+            quote! {{ let value = &#value; #(#calls)* }}
+        };
+        let nested = Self::get_nested(field_options);
+        Self::add_checks(
+            &mut self.checks,
+            field_options,
+            quote! { #validations #nested },
+        );
+    }
+
+    /// The validations of nested fields are left to their base, since the generated struct holds
+    /// the generated struct of the nested one instead.
+    fn add_patch_checks(&mut self, field_options: &FieldOptions) {
+        if field_options.in_variant {
+            return;
+        }
+        let value = field_options.access("self", false);
+        let calls = Self::get_calls(field_options);
+        let is_wrapped = field_options.wrapping_behavior;
+        let validations = match (
+            field_options.validations.is_empty() || field_options.new_type.is_some(),
+            field_options.is_base_opt,
+            is_wrapped,
+        ) {
+            (true, _, _) => quote! {},
+            (false, false, false) => quote! {{ let value = &#value; #(#calls)* }},
+            (false, true, true) => quote! { if let Some(Some(value)) = &#value { #(#calls)* } },
+            (false, _, _) => quote! { if let Some(value) = &#value { #(#calls)* } },
+        };
+        let nested = Self::get_nested(field_options);
+        Self::add_checks(
+            &mut self.patch_checks,
+            field_options,
+            quote! { #validations #nested },
+        );
+    }
+}
+
+impl OptionalFieldVisitor for GenerateValidateVisitor {
+    fn visit(
+        &mut self,
+        _global_options: &GlobalOptions,
+        _old_field: &mut Field,
+        _new_field: &mut Field,
+        field_options: &FieldOptions,
+    ) {
+        self.add_base_checks(field_options);
+        self.add_patch_checks(field_options);
+    }
+
+    // The base is validated as a whole, whatever the view
+    fn visit_excluded(&mut self, _global_options: &GlobalOptions, field_options: &FieldOptions) {
+        self.add_base_checks(field_options);
+    }
+}
+
+/// Generates the `FromEnv` impl, for the views using `env = true`.
+struct GenerateFromEnvVisitor {
+    field_inits: TokenStream,
+}
+
+impl GenerateFromEnvVisitor {
+    fn new() -> Self {
+        GenerateFromEnvVisitor {
+            field_inits: quote! {},
+        }
+    }
+
+    fn get_implementation(self, global_options: &GlobalOptions, new: &DeriveInput) -> TokenStream {
+        let Some(separator) = &global_options.env_separator else {
+            return quote! {};
+        };
+        let (impl_generics, ty_generics, where_clause) = new.generics.split_for_impl();
+        let new_name = &new.ident;
+        let field_inits = self.field_inits;
+        quote! {
+            impl #impl_generics optional_struct::FromEnv for #new_name #ty_generics #where_clause {
+                #[allow(unused_variables)]
+                fn from_env_with<S: optional_struct::EnvSource + ?Sized>(
+                    prefix: &str,
+                    source: &S,
+                ) -> Result<Self, optional_struct::EnvError> {
+                    let separator = #separator;
+                    Ok(Self { #field_inits })
+                }
+            }
+        }
+    }
+}
+
+impl OptionalFieldVisitor for GenerateFromEnvVisitor {
+    fn visit(
+        &mut self,
+        _global_options: &GlobalOptions,
+        _old_field: &mut Field,
+        _new_field: &mut Field,
+        field_options: &FieldOptions,
+    ) {
+        let member = &field_options.field_ident;
+        let cfg_attr = &field_options.cfg_attribute;
+        let name = match &field_options.env_variable {
+            EnvVariable::Default => {
+                let name = field_name(field_options).to_uppercase();
+                quote! { &optional_struct::__private::env_name(prefix, separator, #name) }
+            }
+            EnvVariable::Named(name) => quote! { #name },
+            EnvVariable::Skip => quote! {},
+        };
+
+        let is_wrapped = field_options.wrapping_behavior;
+        let is_nested = field_options.new_type.is_some();
+        let is_base_opt = field_options.is_base_opt;
+        let parse = quote! { optional_struct::__private::parse_env(source, #name)? };
+        let from_env = quote! { optional_struct::FromEnv::from_env_with(#name, source)? };
+        let value = match (
+            &field_options.env_variable,
+            is_base_opt,
+            is_wrapped,
+            is_nested,
+        ) {
+            (EnvVariable::Skip, ..) => quote! { Default::default() },
+            // Set iff one of its variables is, since the fields that are always set would make it
+            // look set otherwise
+            (_, _, true, true) => quote! {{
+                let source = &optional_struct::__private::EnvReadTracker::new(source);
+                let nested = #from_env;
+                if source.was_read() { Some(nested) } else { None }
+            }},
+            (_, _, false, true) => from_env,
+            (_, true, true, false) => quote! { #parse.map(Some) },
+            (_, _, true, false) | (_, true, false, false) => parse,
+            (_, false, false, false) => quote! { #parse.unwrap_or_default() },
+        };
+
+        let field_inits = &self.field_inits;
+        self.field_inits = quote! {
+            #field_inits
+            #cfg_attr
+            #member: #value,
+        };
+    }
+}
+
+/// Generates `ClapArgs`, `clap::Args` and `clap::FromArgMatches`, for the views using
+/// `clap = true`.
+struct GenerateClapVisitor {
+    augment_args: TokenStream,
+    field_inits: TokenStream,
+}
+
+impl GenerateClapVisitor {
+    fn new() -> Self {
+        GenerateClapVisitor {
+            augment_args: quote! {},
+            field_inits: quote! {},
+        }
+    }
+
+    fn get_implementation(self, global_options: &GlobalOptions, new: &DeriveInput) -> TokenStream {
+        if !global_options.clap {
+            return quote! {};
+        }
+        let (impl_generics, ty_generics, where_clause) = new.generics.split_for_impl();
+        let new_name = &new.ident;
+        let augment_args = self.augment_args;
+        let field_inits = self.field_inits;
+        let clap = quote! { optional_struct::__private::clap };
+        quote! {
+            optional_struct::__private::clap_impls! {
+                impl #impl_generics optional_struct::ClapArgs for #new_name #ty_generics #where_clause {
+                    #[allow(unused_variables)]
+                    fn augment_args_prefixed(cmd: #clap::Command, prefix: &str) -> #clap::Command {
+                        #augment_args
+                        cmd
+                    }
+
+                    #[allow(unused_variables)]
+                    fn from_arg_matches_prefixed(
+                        matches: &mut #clap::ArgMatches,
+                        prefix: &str,
+                    ) -> Result<Self, #clap::Error> {
+                        Ok(Self { #field_inits })
+                    }
+                }
+
+                impl #impl_generics #clap::FromArgMatches for #new_name #ty_generics #where_clause {
+                    fn from_arg_matches(matches: &#clap::ArgMatches) -> Result<Self, #clap::Error> {
+                        Self::from_arg_matches_mut(&mut matches.clone())
+                    }
+
+                    fn from_arg_matches_mut(matches: &mut #clap::ArgMatches) -> Result<Self, #clap::Error> {
+                        optional_struct::ClapArgs::from_arg_matches_prefixed(matches, "")
+                    }
+
+                    fn update_from_arg_matches(&mut self, matches: &#clap::ArgMatches) -> Result<(), #clap::Error> {
+                        self.update_from_arg_matches_mut(&mut matches.clone())
+                    }
+
+                    fn update_from_arg_matches_mut(&mut self, matches: &mut #clap::ArgMatches) -> Result<(), #clap::Error> {
+                        let other = Self::from_arg_matches_mut(matches)?;
+                        optional_struct::Applicable::apply_to_opt(other, self);
+                        Ok(())
+                    }
+                }
+
+                impl #impl_generics #clap::Args for #new_name #ty_generics #where_clause {
+                    fn augment_args(cmd: #clap::Command) -> #clap::Command {
+                        <Self as optional_struct::ClapArgs>::augment_args_prefixed(cmd, "")
+                    }
+
+                    fn augment_args_for_update(cmd: #clap::Command) -> #clap::Command {
+                        <Self as optional_struct::ClapArgs>::augment_args_prefixed(cmd, "")
+                    }
+                }
+            }
+        }
+    }
+
+    /// The doc comment of the original field, used as the help of the argument.
+    fn get_help(field: &Field) -> String {
+        let lines = field
+            .attrs
+            .iter()
+            .filter(|a| a.path().is_ident("doc"))
+            .filter_map(|a| match &a.meta {
+                Meta::NameValue(syn::MetaNameValue {
+                    value:
+                        syn::Expr::Lit(syn::ExprLit {
+                            lit: syn::Lit::Str(doc),
+                            ..
+                        }),
+                    ..
+                }) => Some(doc.value().trim().to_owned()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        lines.join("\n").trim().to_owned()
+    }
+}
+
+impl OptionalFieldVisitor for GenerateClapVisitor {
+    fn visit(
+        &mut self,
+        _global_options: &GlobalOptions,
+        old_field: &mut Field,
+        _new_field: &mut Field,
+        field_options: &FieldOptions,
+    ) {
+        let member = &field_options.field_ident;
+        let cfg_attr = &field_options.cfg_attribute;
+        let name = field_name(field_options).replace('_', "-");
+        let clap_options = &field_options.clap_options;
+        let private = quote! { optional_struct::__private };
+
+        let is_wrapped = field_options.wrapping_behavior;
+        let is_base_opt = field_options.is_base_opt;
+        // The type parsed from the command line, e.g. `T` for `Option<T>`
+        let value_type = if is_base_opt {
+            option_inner_type(&old_field.ty)
+        } else {
+            Some(&old_field.ty)
+        };
+        let (augment_args, value) = match (&field_options.new_type, value_type) {
+            _ if clap_options.skip => (quote! {}, quote! { Default::default() }),
+            // Fields that are always set in the generated struct are not arguments
+            (None, _) if !is_wrapped && !is_base_opt => (quote! {}, quote! { Default::default() }),
+            (Some(_), _) if !clap_options.calls.is_empty() => {
+                let error = syn::Error::new_spanned(
+                    member,
+                    format!("'{CLAP_ATTRIBUTE}' can only skip nested fields"),
+                )
+                .to_compile_error();
+                (error, quote! { Default::default() })
+            }
+            (Some(new_type), _) => {
+                let nested_prefix = quote! { &#private::nested_clap_prefix(prefix, #name) };
+                let from_matches = quote! {
+                    <#new_type as optional_struct::ClapArgs>::from_arg_matches_prefixed(matches, #nested_prefix)?
+                };
+                let augment_args = quote! {
+                    <#new_type as optional_struct::ClapArgs>::augment_args_prefixed(cmd, #nested_prefix)
+                };
+                // Set iff one of its arguments is given, see `FromEnv`. Its arguments are grouped
+                // under its prefix, which cannot be the id of an argument.
+                if is_wrapped {
+                    (
+                        quote! {
+                            #cfg_attr
+                            let cmd = #private::clap_nested_group(cmd, #nested_prefix, |cmd| #augment_args);
+                        },
+                        quote! {{
+                            let is_given = #private::clap_group_is_given(matches, #nested_prefix);
+                            let nested = #from_matches;
+                            if is_given { Some(nested) } else { None }
+                        }},
+                    )
+                } else {
+                    (
+                        quote! {
+                            #cfg_attr
+                            let cmd = #augment_args;
+                        },
+                        from_matches,
+                    )
+                }
+            }
+            (None, None) => {
+                let error = syn::Error::new_spanned(
+                    &old_field.ty,
+                    format!("the type of the values of this field is unknown, use '{CLAP_ATTRIBUTE}(skip)'"),
+                )
+                .to_compile_error();
+                (error, quote! { Default::default() })
+            }
+            (None, Some(ty)) => {
+                let help = Self::get_help(old_field);
+                let arg = if is_bool(ty) {
+                    quote! { #private::clap_flag(prefix, #name, #help) }
+                } else {
+                    quote! { #private::clap_arg(prefix, #name, #help) }
+                };
+                let calls = clap_options
+                    .calls
+                    .iter()
+                    .map(|(method, args)| quote! { .#method(#args) });
+                // `Vec`s are given by repeating the argument
+                let (arg, value, parsed_type) = match vec_inner_type(ty) {
+                    Some(element) => (
+                        quote! { #arg.action(#private::clap::ArgAction::Append) },
+                        quote! { #private::clap_values::<#element>(matches, prefix, #name) },
+                        element,
+                    ),
+                    None => (
+                        arg,
+                        quote! { #private::clap_value::<#ty>(matches, prefix, #name) },
+                        ty,
+                    ),
+                };
+                let value = if is_wrapped && is_base_opt {
+                    quote! { #value.map(Some) }
+                } else {
+                    value
+                };
+                // Types that cannot be parsed are reported on the field
+                let value_parser = quote_spanned! {parsed_type.span()=>
+                    optional_struct::__private::clap::value_parser!(#parsed_type)
+                };
+                (
+                    quote! {
+                        #cfg_attr
+                        let cmd = cmd.arg(
+                            #arg
+                                .value_parser(#value_parser)
+                                #(#calls)*
+                        );
+                    },
+                    value,
+                )
+            }
+        };
+
+        self.augment_args.extend(augment_args);
+        let field_inits = &self.field_inits;
+        self.field_inits = quote! {
+            #field_inits
+            #cfg_attr
+            #member: #value,
+        };
+    }
+}
+
+struct SetNewFieldVisibilityVisitor;
+
+impl OptionalFieldVisitor for SetNewFieldVisibilityVisitor {
+    fn visit(
+        &mut self,
+        global_options: &GlobalOptions,
+        _old_field: &mut Field,
+        new_field: &mut Field,
+        field_options: &FieldOptions,
+    ) {
+        // Fields of enum variants cannot have a visibility
+        if field_options.in_variant {
+            return;
+        }
+        if let Some(vis) = &global_options.field_visibility {
+            new_field.vis = vis.clone();
+        }
+    }
+}
+
+struct SetNewFieldTypeVisitor;
+
+impl OptionalFieldVisitor for SetNewFieldTypeVisitor {
+    fn visit(
+        &mut self,
+        _global_options: &GlobalOptions,
+        old_field: &mut Field,
+        new_field: &mut Field,
+        field_options: &FieldOptions,
+    ) {
+        let mut new_type = if let Some(t) = &field_options.new_type {
+            quote! {#t}
+        } else {
+            let t = &old_field.ty;
+            quote! {#t}
+        };
+
+        if field_options.wrapping_behavior {
+            new_type = quote! {Option<#new_type>};
+        };
+        new_field.ty = Type::Verbatim(new_type);
+    }
+}
+
+struct AddSerdeSkipAttribute;
+
+impl OptionalFieldVisitor for AddSerdeSkipAttribute {
+    fn visit(
+        &mut self,
+        _global_options: &GlobalOptions,
+        _old_field: &mut Field,
+        new_field: &mut Field,
+        field_options: &FieldOptions,
+    ) {
+        if !field_options.serde_skip {
+            return;
+        }
+
+        let attribute: Attribute =
+            parse_quote! { #[serde(skip_serializing_if = "Option::is_none")] };
+        new_field.attrs.push(attribute);
+    }
+}
+
+// https://github.com/rust-lang/rust/issues/65823 :(
+struct RemoveHelperAttributesVisitor;
+
+impl OptionalFieldVisitor for RemoveHelperAttributesVisitor {
+    fn visit(
+        &mut self,
+        _global_options: &GlobalOptions,
+        old_field: &mut Field,
+        new_field: &mut Field,
+        field_options: &FieldOptions,
+    ) {
+        field_options
+            .attribute_forwarding
+            .apply(&mut old_field.attrs, &mut new_field.attrs);
+    }
+}
+
+fn is_helper_meta(meta: &Meta) -> bool {
+    HELPER_ATTRIBUTES
+        .iter()
+        .any(|helper| meta.path().is_ident(helper))
+}
+
+fn is_helper_attribute(attribute: &Attribute) -> bool {
+    is_helper_meta(&attribute.meta)
+}
+
+/// Whether the type is `bool`, possibly written as `core::primitive::bool` or similar.
+fn is_bool(ty: &Type) -> bool {
+    let Type::Path(TypePath { qself: None, path }) = ty else {
+        return false;
+    };
+    let segments = path.segments.iter().collect::<Vec<_>>();
+    let is_primitive = match segments.as_slice() {
+        [_] => path.leading_colon.is_none(),
+        [.., module, _] => module.ident == "primitive",
+        [] => false,
+    };
+    is_primitive
+        && segments
+            .last()
+            .is_some_and(|s| s.ident == "bool" && s.arguments.is_none())
+}
+
+fn is_same_path(a: &Path, b: &Path) -> bool {
+    a.segments.len() == b.segments.len()
+        && a.segments
+            .iter()
+            .zip(b.segments.iter())
+            .all(|(a, b)| a.ident == b.ident)
+}
+
+/// Which attributes of the original struct (or field, or variant) are added to, or kept off, the
+/// generated one.
+#[derive(Default)]
+struct AttributeForwarding {
+    added: Vec<Attribute>,
+    skipped: Vec<Path>,
+}
+
+impl AttributeForwarding {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut out = AttributeForwarding::default();
+        let mut errors = ErrorAccumulator::default();
+        for a in attrs {
+            if a.path().is_ident(ATTR_ATTRIBUTE) {
+                let metas = a.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated);
+                if let Some(metas) = errors.handle(metas) {
+                    out.added
+                        .extend(metas.into_iter().map(|meta| parse_quote! { #[#meta] }));
+                }
+            } else if a.path().is_ident(SKIP_ATTR_ATTRIBUTE) {
+                let paths = a.parse_args_with(Punctuated::<Path, Token![,]>::parse_terminated);
+                if let Some(paths) = errors.handle(paths) {
+                    out.skipped.extend(paths);
+                }
+            }
+        }
+        errors.finish()?;
+        Ok(out)
+    }
+
+    /// Strips our helper attributes from both lists, while preserving the order of the others.
+    fn apply(&self, old_attrs: &mut Vec<Attribute>, new_attrs: &mut Vec<Attribute>) {
+        old_attrs.retain(|a| !is_helper_attribute(a));
+        new_attrs.retain(|a| {
+            !is_helper_attribute(a) && !self.skipped.iter().any(|p| is_same_path(a.path(), p))
+        });
+        new_attrs.extend(self.added.iter().cloned());
+    }
+}
+
+/// Collects several errors so they can all be reported at once, instead of stopping at the first
+/// one.
+#[derive(Default)]
+struct ErrorAccumulator(Option<syn::Error>);
+
+impl ErrorAccumulator {
+    fn push(&mut self, error: syn::Error) {
+        match &mut self.0 {
+            Some(acc) => acc.combine(error),
+            None => self.0 = Some(error),
+        }
+    }
+
+    fn handle<T>(&mut self, result: syn::Result<T>) -> Option<T> {
+        result.map_err(|e| self.push(e)).ok()
+    }
+
+    fn finish(self) -> syn::Result<()> {
+        match self.0 {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+fn check_data(derive_input: &DeriveInput) -> syn::Result<()> {
+    match &derive_input.data {
+        Data::Struct(data_struct) if matches!(data_struct.fields, Fields::Unit) => {
+            Err(syn::Error::new(
+                derive_input.ident.span(),
+                "optional_struct cannot be used on a unit struct, as it has no fields to make optional",
+            ))
+        }
+        Data::Enum(data_enum) if data_enum.variants.is_empty() => Err(syn::Error::new(
+            derive_input.ident.span(),
+            "optional_struct cannot be used on an enum without variants",
+        )),
+        Data::Union(u) => Err(syn::Error::new(
+            u.union_token.span,
+            "optional_struct only works for structs and enums",
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn set_new_type(
+    new_type: &mut Option<Type>,
+    ty: Type,
+    attribute: &Attribute,
+    errors: &mut ErrorAccumulator,
+) {
+    if new_type.is_some() {
+        errors.push(syn::Error::new_spanned(
+            attribute,
+            format!("'{RENAME_ATTRIBUTE}' and '{NESTED_ATTRIBUTE}' cannot be used together, or more than once"),
+        ));
+    }
+    *new_type = Some(ty);
+}
+
+fn parse_field_options(
+    global_options: &GlobalOptions,
+    field: &Field,
+    struct_index: usize,
+    in_variant: bool,
+) -> syn::Result<FieldOptions> {
+    let mut errors = ErrorAccumulator::default();
+    let attribute_forwarding = errors
+        .handle(AttributeForwarding::parse(&field.attrs))
+        .unwrap_or_default();
+    let mut wrapping_override = None;
+    let mut is_option_override = None;
+    let mut cfg_attribute = quote! {};
+    let mut new_type = None;
+    let mut serde_skip = false;
+    let mut view_filter = None;
+    let mut default_value = None;
+    let mut merge_with = None;
+    let mut validations = Vec::new();
+    let mut env_variable = EnvVariable::Default;
+    let mut clap_options = ClapOptions::default();
+    for a in &field.attrs {
+        if a.path().is_ident(RENAME_ATTRIBUTE) {
+            let args = a.parse_args().map_err(|_| {
+                syn::Error::new_spanned(
+                    a,
+                    format!("'{RENAME_ATTRIBUTE}' attribute expects one and only one argument (the new type to use)"),
+                )
+            });
+            if let Some(args) = errors.handle(args) {
+                set_new_type(&mut new_type, args, a, &mut errors);
+            }
+        } else if a.path().is_ident(NESTED_ATTRIBUTE) {
+            errors.handle(a.meta.require_path_only());
+            let ty = &field.ty;
+            // Spanned on the type, which is where a missing `HasOptional` impl is reported
+            let nested = parse_quote_spanned! {ty.span()=>
+                <#ty as optional_struct::HasOptional>::Optional
+            };
+            set_new_type(&mut new_type, nested, a, &mut errors);
+        } else if a.path().is_ident(SKIP_WRAP_ATTRIBUTE) {
+            errors.handle(a.meta.require_path_only());
+            wrapping_override = Some(false);
+        } else if a.path().is_ident(WRAP_ATTRIBUTE) {
+            errors.handle(a.meta.require_path_only());
+            wrapping_override = Some(true);
+        } else if a.path().is_ident(IS_OPTION_ATTRIBUTE) || a.path().is_ident(NOT_OPTION_ATTRIBUTE)
+        {
+            errors.handle(a.meta.require_path_only());
+            let is_option = a.path().is_ident(IS_OPTION_ATTRIBUTE);
+            if is_option_override.is_some_and(|o| o != is_option) {
+                errors.push(syn::Error::new_spanned(
+                    a,
+                    format!("'{IS_OPTION_ATTRIBUTE}' and '{NOT_OPTION_ATTRIBUTE}' are mutually exclusive"),
+                ));
+            }
+            is_option_override = Some(is_option);
+        } else if a.path().is_ident(SERDE_SKIP_SERIALIZING_NONE) {
+            errors.handle(a.meta.require_path_only());
+            serde_skip = true;
+        } else if a.path().is_ident(INCLUDE_ATTRIBUTE) || a.path().is_ident(EXCLUDE_ATTRIBUTE) {
+            let is_include = a.path().is_ident(INCLUDE_ATTRIBUTE);
+            if field.ident.is_none() || in_variant {
+                errors.push(syn::Error::new_spanned(
+                    a,
+                    "views can only leave out named fields of structs",
+                ));
+            } else if view_filter.is_some() {
+                errors.push(syn::Error::new_spanned(
+                    a,
+                    format!("'{INCLUDE_ATTRIBUTE}' and '{EXCLUDE_ATTRIBUTE}' cannot be used together, or more than once"),
+                ));
+            }
+            let views = a.parse_args_with(Punctuated::<Ident, Token![,]>::parse_terminated);
+            if let Some(views) = errors.handle(views) {
+                for view in &views {
+                    if !global_options.view_names.iter().any(|v| view == v) {
+                        errors.push(syn::Error::new(
+                            view.span(),
+                            format!("unknown view `{view}`, expected one of the generated structs' names"),
+                        ));
+                    }
+                }
+                let listed = views
+                    .iter()
+                    .any(|view| *view == global_options.new_struct_name);
+                view_filter = Some(listed == is_include);
+            }
+        } else if a.path().is_ident(DEFAULT_ATTRIBUTE) {
+            let tokens = a.meta.require_list().map(|l| l.tokens.clone());
+            match errors.handle(tokens) {
+                Some(tokens) if !tokens.is_empty() && !in_variant && default_value.is_none() => {
+                    default_value = Some(tokens)
+                }
+                Some(_) => errors.push(syn::Error::new_spanned(
+                    a,
+                    format!("'{DEFAULT_ATTRIBUTE}' expects one expression, and can only be used once on fields of structs"),
+                )),
+                None => {}
+            }
+        } else if a.path().is_ident(MERGE_ATTRIBUTE) {
+            if merge_with.is_some() {
+                errors.push(syn::Error::new_spanned(
+                    a,
+                    format!("'{MERGE_ATTRIBUTE}' can only be used once"),
+                ));
+            }
+            merge_with = errors.handle(a.parse_args_with(parse_merge_strategy));
+        } else if a.path().is_ident(VALIDATE_ATTRIBUTE) {
+            if in_variant {
+                errors.push(syn::Error::new_spanned(
+                    a,
+                    format!("'{VALIDATE_ATTRIBUTE}' can only be used on fields of structs"),
+                ));
+            }
+            let parsed = a.parse_args_with(Punctuated::<Validation, Token![,]>::parse_terminated);
+            if let Some(parsed) = errors.handle(parsed) {
+                validations.extend(parsed);
+            }
+        } else if a.path().is_ident(ENV_ATTRIBUTE) {
+            let parsed = a.parse_args_with(|input: ParseStream| {
+                if input.peek(syn::LitStr) {
+                    Ok(EnvVariable::Named(input.parse()?))
+                } else {
+                    let ident: Ident = input.parse()?;
+                    if ident != "skip" {
+                        return Err(syn::Error::new(
+                            ident.span(),
+                            format!(
+                                "'{ENV_ATTRIBUTE}' expects the name of the variable, or `skip`"
+                            ),
+                        ));
+                    }
+                    Ok(EnvVariable::Skip)
+                }
+            });
+            if !matches!(env_variable, EnvVariable::Default) {
+                errors.push(syn::Error::new_spanned(
+                    a,
+                    format!("'{ENV_ATTRIBUTE}' can only be used once"),
+                ));
+            }
+            if let Some(parsed) = errors.handle(parsed) {
+                env_variable = parsed;
+            }
+        } else if a.path().is_ident(CLAP_ATTRIBUTE) {
+            if let Some(parsed) = errors.handle(a.parse_args::<ClapOptions>()) {
+                clap_options.skip |= parsed.skip;
+                clap_options.calls.extend(parsed.calls);
+            }
+        } else if a.path().is_ident(CFG_ATTRIBUTE) {
+            cfg_attribute.extend(quote! { #a });
+        }
+    }
+    if let (Some(_), Some(a)) = (&new_type, merge_attribute(field)) {
+        errors.push(syn::Error::new_spanned(
+            a,
+            format!("'{MERGE_ATTRIBUTE}' cannot be used on nested or renamed fields"),
+        ));
+    }
+
+    let is_base_opt = match is_option_override {
+        Some(is_option) => is_option,
+        None => errors.handle(is_type_option(&field.ty)).unwrap_or(false),
+    };
+    errors.finish()?;
+
+    // Nested structures are not wrapped unless explicitly asked to
+    let wrapping_behavior = wrapping_override
+        .unwrap_or(new_type.is_none() && !is_base_opt && global_options.default_wrapping_behavior);
+
+    // Fields that are always set (and in every view) never fall back on their default, unless
+    // they are merged with it
+    let is_always_set = !wrapping_behavior && new_type.is_none() && !is_base_opt;
+    if default_value.is_some() && is_always_set && merge_with.is_none() && view_filter.is_none() {
+        let a = field
+            .attrs
+            .iter()
+            .find(|a| a.path().is_ident(DEFAULT_ATTRIBUTE));
+        return Err(syn::Error::new_spanned(
+            a,
+            format!("'{DEFAULT_ATTRIBUTE}' cannot be used on fields that are always set, unless they are merged with '{MERGE_ATTRIBUTE}'"),
+        ));
+    }
+
+    let field_ident = if let Some(ident) = &field.ident {
+        quote! {#ident}
+    } else {
+        let i = syn::Index::from(struct_index);
+        quote! {#i}
+    };
+    Ok(FieldOptions {
+        wrapping_behavior,
+        is_base_opt,
+        cfg_attribute,
+        new_type,
+        field_ident,
+        field_index: struct_index,
+        in_variant,
+        in_view: view_filter.unwrap_or(true),
+        default_value,
+        merge_with,
+        validations,
+        env_variable,
+        clap_options,
+        serde_skip,
+        attribute_forwarding,
+    })
+}
+
+/// Parses one of the validations of `optional_struct::validate` (e.g. `non_empty`, or
+/// `range(1..=65535)`), or `with = path::to::function`.
+impl Parse for Validation {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident: Ident = input.parse()?;
+        if ident == "with" {
+            input.parse::<Token![=]>()?;
+            let path: Path = input.parse()?;
+            return Ok(Validation {
+                function: quote! { #path },
+                extra_args: quote! {},
+            });
+        }
+        let function = quote! { optional_struct::validate::#ident };
+        if ident == "non_empty" {
+            Ok(Validation {
+                function,
+                extra_args: quote! {},
+            })
+        } else if ident == "range" {
+            let content;
+            syn::parenthesized!(content in input);
+            let range: TokenStream = content.parse()?;
+            if range.is_empty() {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "`range` expects a range, e.g. `range(1..=65535)`",
+                ));
+            }
+            Ok(Validation {
+                function,
+                extra_args: quote! { , #range },
+            })
+        } else {
+            Err(syn::Error::new(
+                ident.span(),
+                format!("unknown validation `{ident}`, expected one of `range(...)`, `non_empty`, or `with = path::to::function`"),
+            ))
         }
-        Type::Infer(_) => panic!("If you cannot tell, neither can I"),
-        Type::Macro(_) => panic!("Don't think I can handle this easily..."),
+    }
+}
 
-        // Makes no sense to use those in an OptionalStruct
-        Type::Reference(_) => wtf!("reference"),
-        Type::Never(_) => wtf!("never-type"),
-        Type::Slice(_) => wtf!("slice"),
-        Type::Ptr(_) => wtf!("pointer"),
-        Type::BareFn(_) => wtf!("function pointer"),
+fn merge_attribute(field: &Field) -> Option<&Attribute> {
+    field
+        .attrs
+        .iter()
+        .find(|a| a.path().is_ident(MERGE_ATTRIBUTE))
+}
+
+/// Parses the content of `optional_merge`, i.e. one of the strategies of `optional_struct::merge`,
+/// or `with = path::to::function`, into the path of the merging function.
+fn parse_merge_strategy(input: ParseStream) -> syn::Result<TokenStream> {
+    const STRATEGIES: &[&str] = &["append", "extend", "max", "min", "sum"];
+    let ident: Ident = input.parse()?;
+    if ident == "with" {
+        input.parse::<Token![=]>()?;
+        let path: Path = input.parse()?;
+        return Ok(quote! { #path });
+    }
+    if !STRATEGIES.iter().any(|s| ident == s) {
+        return Err(syn::Error::new(
+            ident.span(),
+            format!(
+                "unknown merge strategy `{ident}`, expected one of {}, or `with = path::to::function`",
+                STRATEGIES
+                    .iter()
+                    .map(|s| format!("`{s}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        ));
+    }
+    Ok(quote! { optional_struct::merge::#ident })
+}
+
+/// Flattens e.g. `#[cfg_attr(a, x, cfg_attr(b, y))]` into `[(a, x), (all(a, b), y)]`.
+fn flatten_cfg_attr(predicate: TokenStream, meta: Meta) -> syn::Result<Vec<(TokenStream, Meta)>> {
+    if !meta.path().is_ident(CFG_ATTR_ATTRIBUTE) {
+        return Ok(vec![(predicate, meta)]);
+    }
+
+    let (inner_predicate, metas) = meta.require_list()?.parse_args_with(|input: ParseStream| {
+        let inner_predicate = input.parse::<Meta>()?;
+        input.parse::<Token![,]>()?;
+        let metas = Punctuated::<Meta, Token![,]>::parse_terminated(input)?;
+        Ok((inner_predicate, metas))
+    })?;
+    let predicate = if predicate.is_empty() {
+        quote! { #inner_predicate }
+    } else {
+        quote! { all(#predicate, #inner_predicate) }
+    };
+    let mut flattened = Vec::new();
+    for meta in metas {
+        flattened.extend(flatten_cfg_attr(predicate.clone(), meta)?);
+    }
+    Ok(flattened)
+}
+
+/// An attribute of a field, where the `cfg_attr` wrapping our helper attributes are flattened.
+enum FieldAttribute<'a> {
+    Plain(&'a Attribute),
+    CfgAttr(Vec<(TokenStream, Meta)>),
+}
+
+/// The macro cannot evaluate the predicates of `cfg_attr` attributes wrapping our helper
+/// attributes. Instead, one version of the field is generated for every combination of those
+/// predicates, each gated by the matching `cfg`.
+/// The helper attributes are always stripped from the original field in that case.
+fn expand_cfg_attr_helpers(field: &mut Field) -> syn::Result<Vec<Field>> {
+    let mut field_attrs = Vec::new();
+    let mut predicates = Vec::<TokenStream>::new();
+    let position = |predicates: &[TokenStream], predicate: &TokenStream| {
+        let predicate = predicate.to_string();
+        predicates.iter().position(|p| p.to_string() == predicate)
+    };
+    for a in &field.attrs {
+        if a.path().is_ident(CFG_ATTR_ATTRIBUTE) {
+            let flattened = flatten_cfg_attr(quote! {}, a.meta.clone())?;
+            let mut has_helpers = false;
+            for (predicate, _) in flattened.iter().filter(|(_, meta)| is_helper_meta(meta)) {
+                has_helpers = true;
+                if position(&predicates, predicate).is_none() {
+                    predicates.push(predicate.clone());
+                }
+            }
+            if has_helpers {
+                field_attrs.push(FieldAttribute::CfgAttr(flattened));
+                continue;
+            }
+        }
+        field_attrs.push(FieldAttribute::Plain(a));
+    }
+    if predicates.is_empty() {
+        return Ok(vec![field.clone()]);
+    }
+
+    // `enabled` tells which predicates hold, `None` meaning the original field
+    let build_attrs = |enabled: Option<&[bool]>| {
+        let mut attrs = Vec::<Attribute>::new();
+        for field_attr in &field_attrs {
+            match field_attr {
+                FieldAttribute::Plain(a) if enabled.is_some() || !is_helper_meta(&a.meta) => {
+                    attrs.push((*a).clone())
+                }
+                FieldAttribute::Plain(_) => {}
+                FieldAttribute::CfgAttr(flattened) => {
+                    for (predicate, meta) in flattened {
+                        if !is_helper_meta(meta) {
+                            attrs.push(parse_quote! { #[cfg_attr(#predicate, #meta)] });
+                            continue;
+                        }
+                        let Some(enabled) = enabled else { continue };
+                        if enabled[position(&predicates, predicate).unwrap()] {
+                            attrs.push(parse_quote! { #[#meta] });
+                        }
+                    }
+                }
+            }
+        }
+        for (predicate, enabled) in predicates.iter().zip(enabled.into_iter().flatten()) {
+            if *enabled {
+                attrs.push(parse_quote! { #[cfg(#predicate)] });
+            } else {
+                attrs.push(parse_quote! { #[cfg(not(#predicate))] });
+            }
+        }
+        attrs
+    };
+
+    let versions = (0..1usize << predicates.len())
+        .map(|mask| {
+            let enabled = (0..predicates.len())
+                .map(|i| mask & (1 << i) != 0)
+                .collect::<Vec<_>>();
+            Field {
+                attrs: build_attrs(Some(&enabled)),
+                ..field.clone()
+            }
+        })
+        .collect();
+    let orig_attrs = build_attrs(None);
+    field.attrs = orig_attrs;
+    Ok(versions)
+}
+
+/// Visits the fields of a struct or variant, and returns the fields of the generated one.
+fn visit_field_list(
+    visitors: &mut [&mut dyn OptionalFieldVisitor],
+    global_options: &GlobalOptions,
+    old_fields: &mut Fields,
+    in_variant: bool,
+    errors: &mut ErrorAccumulator,
+) -> Vec<Field> {
+    let mut new_fields = Vec::new();
+    for (struct_index, old_field) in old_fields.iter_mut().enumerate() {
+        let Some(versions) = errors.handle(expand_cfg_attr_helpers(old_field)) else {
+            continue;
+        };
+        let is_expanded = versions.len() > 1;
+        for mut new_field in versions {
+            let mut old_version = new_field.clone();
+            let Some(field_options) = errors.handle(parse_field_options(
+                global_options,
+                &old_version,
+                struct_index,
+                in_variant,
+            )) else {
+                continue;
+            };
+            if !field_options.in_view {
+                old_version.attrs.retain(|a| !is_helper_attribute(a));
+                for v in &mut *visitors {
+                    v.visit_excluded(global_options, &field_options);
+                }
+                if !is_expanded {
+                    *old_field = old_version;
+                }
+                continue;
+            }
+            for v in &mut *visitors {
+                v.visit(
+                    global_options,
+                    &mut old_version,
+                    &mut new_field,
+                    &field_options,
+                );
+            }
+            if !is_expanded {
+                *old_field = old_version;
+            }
+            new_fields.push(new_field);
+        }
+    }
+    new_fields
+}
+
+fn set_fields(fields: &mut Fields, new_fields: Vec<Field>) {
+    let mut punctuated = new_fields
+        .into_iter()
+        .collect::<Punctuated<Field, Token![,]>>();
+    if !punctuated.empty_or_trailing() {
+        punctuated.push_punct(Default::default());
+    }
+    match fields {
+        Fields::Named(f) => f.named = punctuated,
+        Fields::Unnamed(f) => f.unnamed = punctuated,
+        Fields::Unit => {}
+    }
+}
+
+fn visit_fields(
+    visitors: &mut [&mut dyn OptionalFieldVisitor],
+    global_options: &GlobalOptions,
+    derive_input: &DeriveInput,
+) -> syn::Result<(DeriveInput, DeriveInput)> {
+    check_data(derive_input)?;
+    let mut new = derive_input.clone();
+    let mut orig = derive_input.clone();
+
+    let mut errors = ErrorAccumulator::default();
+    if let Some(forwarding) = errors.handle(AttributeForwarding::parse(&derive_input.attrs)) {
+        forwarding.apply(&mut orig.attrs, &mut new.attrs);
+    }
+    match (&mut orig.data, &mut new.data) {
+        (Data::Struct(old_struct), Data::Struct(new_struct)) => {
+            let new_fields = visit_field_list(
+                visitors,
+                global_options,
+                &mut old_struct.fields,
+                false,
+                &mut errors,
+            );
+            set_fields(&mut new_struct.fields, new_fields);
+        }
+        (Data::Enum(old_enum), Data::Enum(new_enum)) => {
+            for (old_variant, new_variant) in old_enum
+                .variants
+                .iter_mut()
+                .zip(new_enum.variants.iter_mut())
+            {
+                if let Some(forwarding) =
+                    errors.handle(AttributeForwarding::parse(&old_variant.attrs))
+                {
+                    forwarding.apply(&mut old_variant.attrs, &mut new_variant.attrs);
+                }
+                let new_fields = visit_field_list(
+                    visitors,
+                    global_options,
+                    &mut old_variant.fields,
+                    true,
+                    &mut errors,
+                );
+                set_fields(&mut new_variant.fields, new_fields);
+                for v in &mut *visitors {
+                    v.end_variant(global_options, &derive_input.ident, old_variant);
+                }
+            }
+        }
+        _ => unreachable!("checked by check_data"),
+    }
+    errors.finish()?;
+    Ok((orig, new))
+}
+
+/// The derive macros available in the prelude, and the path they resolve to.
+const PRELUDE_DERIVES: &[(&str, &str)] = &[
+    ("Clone", "core::clone::Clone"),
+    ("Copy", "core::marker::Copy"),
+    ("Debug", "core::fmt::Debug"),
+    ("Default", "core::default::Default"),
+    ("Eq", "core::cmp::Eq"),
+    ("Hash", "core::hash::Hash"),
+    ("Ord", "core::cmp::Ord"),
+    ("PartialEq", "core::cmp::PartialEq"),
+    ("PartialOrd", "core::cmp::PartialOrd"),
+];
+
+/// Best effort at resolving the path of a derive macro without type information, so that e.g.
+/// `Hash`, `std::hash::Hash` and `::core::hash::Hash` are recognized as the same derive.
+fn resolve_derive_path(path: &Path) -> String {
+    let mut segments = path
+        .segments
+        .iter()
+        .map(|s| s.ident.to_string())
+        .collect::<Vec<_>>();
+    if let [single] = segments.as_slice() {
+        if let Some((_, resolved)) = PRELUDE_DERIVES.iter().find(|(name, _)| name == single) {
+            return resolved.to_string();
+        }
+    }
+    if let Some(root) = segments.first_mut() {
+        if root == "std" || root == "alloc" {
+            *root = "core".to_owned();
+        }
+    }
+    segments.join("::")
+}
+
+/// Whether the generated struct derives the trait with the given resolved path (see
+/// `resolve_derive_path`).
+fn is_derived(new: &DeriveInput, extra_derive: &[Path], resolved: &str) -> bool {
+    let mut is_derived = extra_derive
+        .iter()
+        .any(|p| resolve_derive_path(p) == resolved);
+    for attributes in new.attrs.iter().filter(|a| a.path().is_ident("derive")) {
+        let _ = attributes.parse_nested_meta(|derived_trait| {
+            is_derived |= resolve_derive_path(&derived_trait.path) == resolved;
+            Ok(())
+        });
+    }
+    is_derived
+}
+
+fn get_derive_macros(new: &DeriveInput, extra_derive: &[Path]) -> TokenStream {
+    let mut already_derived = HashSet::new();
+    for attributes in new.attrs.iter().filter(|a| a.path().is_ident("derive")) {
+        let _ = attributes.parse_nested_meta(|derived_trait| {
+            already_derived.insert(resolve_derive_path(&derived_trait.path));
+            Ok(())
+        });
+    }
+
+    let left_to_derive = extra_derive
+        .iter()
+        .filter(|p| already_derived.insert(resolve_derive_path(p)))
+        .collect::<Vec<_>>();
+    if left_to_derive.is_empty() {
+        return quote! {};
+    }
+
+    quote! { #[derive(#(#left_to_derive),*)] }
+}
+
+#[derive(Clone)]
+struct ParsedMacroParameters {
+    new_struct_name: Option<String>,
+    /// The names following the first one in the positional form, e.g.
+    /// `#[optional_struct(ConfigFileLayer, CliLayer)]`, each generating another view with the same
+    /// parameters.
+    other_names: Vec<String>,
+    default_wrapping: bool,
+    derives: Option<Vec<Path>>,
+    /// `None` keeps the visibility of the original struct.
+    struct_visibility: Option<Visibility>,
+    /// `None` keeps the visibility of the original fields.
+    field_visibility: Option<Visibility>,
+    extra_bounds: Vec<WherePredicate>,
+    default_base: Option<Path>,
+    env: bool,
+    env_separator: Option<syn::LitStr>,
+    clap: bool,
+}
+
+impl ParsedMacroParameters {
+    /// Either a visibility, or `inherit` (returning `None`) to keep the original one.
+    fn parse_visibility(input: ParseStream) -> syn::Result<Option<Visibility>> {
+        if input.peek(Ident) && input.fork().parse::<Ident>()? == INHERIT_VISIBILITY {
+            input.parse::<Ident>()?;
+            return Ok(None);
+        }
+        match input.parse::<Visibility>()? {
+            Visibility::Inherited => Err(input.error(format!(
+                "expected a visibility (e.g. `pub(crate)`, or `pub(self)` for private) or `{INHERIT_VISIBILITY}`"
+            ))),
+            vis => Ok(Some(vis)),
+        }
+    }
+
+    const PARAMETERS: &'static [&'static str] = &[
+        NAME_PARAMETER,
+        DEFAULT_WRAP_PARAMETER,
+        DERIVE_PARAMETER,
+        VISIBILITY_PARAMETER,
+        FIELD_VISIBILITY_PARAMETER,
+        BOUND_PARAMETER,
+        DEFAULT_PARAMETER,
+        ENV_PARAMETER,
+        ENV_SEPARATOR_PARAMETER,
+        CLAP_PARAMETER,
+    ];
+
+    fn parse_named_parameter(&mut self, key: &Ident, input: ParseStream) -> syn::Result<()> {
+        if key == NAME_PARAMETER {
+            input.parse::<Token![=]>()?;
+            self.new_struct_name = Some(input.parse::<Ident>()?.to_string());
+        } else if key == DEFAULT_WRAP_PARAMETER {
+            input.parse::<Token![=]>()?;
+            self.default_wrapping = input.parse::<syn::LitBool>()?.value;
+        } else if key == DERIVE_PARAMETER {
+            let content;
+            syn::parenthesized!(content in input);
+            let derives = Punctuated::<Path, Token![,]>::parse_terminated(&content)?;
+            self.derives = Some(derives.into_iter().collect());
+        } else if key == VISIBILITY_PARAMETER {
+            input.parse::<Token![=]>()?;
+            self.struct_visibility = Self::parse_visibility(input)?;
+        } else if key == FIELD_VISIBILITY_PARAMETER {
+            input.parse::<Token![=]>()?;
+            self.field_visibility = Self::parse_visibility(input)?;
+        } else if key == BOUND_PARAMETER {
+            input.parse::<Token![=]>()?;
+            let bounds = input
+                .parse::<syn::LitStr>()?
+                .parse_with(Punctuated::<WherePredicate, Token![,]>::parse_terminated)?;
+            self.extra_bounds = bounds.into_iter().collect();
+        } else if key == DEFAULT_PARAMETER {
+            input.parse::<Token![=]>()?;
+            self.default_base = Some(input.parse::<Path>()?);
+        } else if key == ENV_PARAMETER {
+            input.parse::<Token![=]>()?;
+            self.env = input.parse::<syn::LitBool>()?.value;
+        } else if key == ENV_SEPARATOR_PARAMETER {
+            input.parse::<Token![=]>()?;
+            self.env_separator = Some(input.parse::<syn::LitStr>()?);
+        } else if key == CLAP_PARAMETER {
+            input.parse::<Token![=]>()?;
+            self.clap = input.parse::<syn::LitBool>()?.value;
+        } else {
+            let expected = Self::PARAMETERS
+                .iter()
+                .map(|p| format!("`{p}`"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(syn::Error::new(
+                key.span(),
+                format!("unknown optional_struct parameter `{key}`, expected one of {expected}"),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Parse for ParsedMacroParameters {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut out = ParsedMacroParameters {
+            new_struct_name: None,
+            other_names: Vec::new(),
+            default_wrapping: true,
+            derives: None,
+            struct_visibility: None,
+            field_visibility: Some(parse_quote!(pub)),
+            extra_bounds: Vec::new(),
+            default_base: None,
+            env: false,
+            env_separator: None,
+            clap: false,
+        };
+
+        let mut seen = HashSet::new();
+
+        // Positional form, e.g. `#[optional_struct(OptionalFoo, false)]`, or
+        // `#[optional_struct(ConfigFileLayer, CliLayer)]` for several views
+        let is_positional_name =
+            input.peek(Ident) && !input.peek2(Token![=]) && !input.peek2(syn::token::Paren);
+        if is_positional_name {
+            out.new_struct_name = Some(input.parse::<Ident>()?.to_string());
+            seen.insert(NAME_PARAMETER.to_owned());
+            while input.peek(Token![,])
+                && input.peek2(Ident)
+                && !input.peek3(Token![=])
+                && !input.peek3(syn::token::Paren)
+            {
+                input.parse::<Token![,]>()?;
+                out.other_names.push(input.parse::<Ident>()?.to_string());
+            }
+            if input.peek(Token![,]) && input.peek2(syn::LitBool) {
+                input.parse::<Token![,]>()?;
+                out.default_wrapping = input.parse::<syn::LitBool>()?.value;
+                seen.insert(DEFAULT_WRAP_PARAMETER.to_owned());
+            }
+        }
+
+        let mut first = !is_positional_name;
+        while !input.is_empty() {
+            if !first {
+                input.parse::<Token![,]>()?;
+                if input.is_empty() {
+                    break;
+                }
+            }
+            first = false;
+
+            let key = input.parse::<Ident>()?;
+            if !seen.insert(key.to_string()) {
+                return Err(syn::Error::new(
+                    key.span(),
+                    format!("duplicate optional_struct parameter `{key}`"),
+                ));
+            }
+            out.parse_named_parameter(&key, input)?;
+        }
+
+        if let (false, Some(separator)) = (out.env, &out.env_separator) {
+            return Err(syn::Error::new_spanned(
+                separator,
+                format!(
+                    "the `{ENV_SEPARATOR_PARAMETER}` parameter requires `{ENV_PARAMETER} = true`"
+                ),
+            ));
+        }
+
+        Ok(out)
+    }
+}
+
+/// The `T` of an `Option<T>`, if the type is written that way (and not e.g. through an alias).
+fn option_inner_type(t: &Type) -> Option<&Type> {
+    single_type_argument(t, is_path_option)
+}
+
+/// The `T` of a `Vec<T>`, if the type is written as `Vec<T>`, `std::vec::Vec<T>` or
+/// `alloc::vec::Vec<T>`.
+fn vec_inner_type(t: &Type) -> Option<&Type> {
+    single_type_argument(t, |p| {
+        let segments = p
+            .segments
+            .iter()
+            .map(|ps| ps.ident.to_string())
+            .collect::<Vec<_>>();
+        match segments.as_slice() {
+            [vec] => p.leading_colon.is_none() && vec == "Vec",
+            [root, module, vec] => {
+                (root == "std" || root == "alloc") && module == "vec" && vec == "Vec"
+            }
+            _ => false,
+        }
+    })
+}
+
+/// The only type argument of `t`, if its path is accepted by `is_expected_path`.
+fn single_type_argument(t: &Type, is_expected_path: impl Fn(&Path) -> bool) -> Option<&Type> {
+    let Type::Path(type_path) = t else {
+        return None;
+    };
+    if type_path.qself.is_some() || !is_expected_path(&type_path.path) {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(args) = &type_path.path.segments.last()?.arguments
+    else {
+        return None;
+    };
+    match args.args.first()? {
+        syn::GenericArgument::Type(inner) if args.args.len() == 1 => Some(inner),
+        _ => None,
+    }
+}
+
+/// Only the paths `Option` (from the prelude), and `std::option::Option` or `core::option::Option`
+/// (possibly with a leading `::`) are considered to be options. Aliases and other types named
+/// `Option` have to be annotated with `optional_is_option` / `optional_not_option`.
+fn is_path_option(p: &Path) -> bool {
+    let segments = p
+        .segments
+        .iter()
+        .map(|ps| ps.ident.to_string())
+        .collect::<Vec<_>>();
+    match segments.as_slice() {
+        [option] => p.leading_colon.is_none() && option == "Option",
+        [root, module, option] => {
+            (root == "std" || root == "core") && module == "option" && option == "Option"
+        }
+        _ => false,
+    }
+}
+
+fn is_type_option(t: &Type) -> syn::Result<bool> {
+    let unsupported = |reason: &str| {
+        Err(syn::Error::new_spanned(
+            t,
+            format!(
+                "optional_struct cannot tell whether this type is an `Option`, as it is {reason}; \
+                 use '{IS_OPTION_ATTRIBUTE}' or '{NOT_OPTION_ATTRIBUTE}' to specify it"
+            ),
+        ))
+    };
+
+    match &t {
+        // real work
+        Type::Path(type_path) => Ok(type_path.qself.is_none() && is_path_option(&type_path.path)),
+        Type::Array(_) | Type::Tuple(_) | Type::Reference(_) | Type::Ptr(_) | Type::BareFn(_) => {
+            Ok(false)
+        }
+        Type::Paren(type_paren) => is_type_option(&type_paren.elem),
+        Type::Group(type_group) => is_type_option(&type_group.elem),
 
-        // Help
-        Type::Verbatim(_) => todo!("Didn't get what this was supposed to be..."),
-        Type::Group(_) => todo!("Not sure what to do here"),
+        // No way to tell whether those are options
+        Type::ImplTrait(_) => unsupported("an `impl Trait`"),
+        Type::TraitObject(_) => unsupported("a trait object"),
+        Type::Infer(_) => unsupported("inferred"),
+        Type::Macro(_) => unsupported("a macro invocation"),
+        Type::Verbatim(_) => unsupported("not understood by syn"),
 
-        // Have to wildcard here but I don't want to (unneeded as long as syn doesn't break semver
-        // anyway)
-        _ => panic!("Open an issue please :)"),
+        // Makes no sense to use those in an OptionalStruct
+        Type::Never(_) => unsupported("the never type"),
+        Type::Slice(_) => unsupported("a slice"),
+
+        _ => unsupported("unknown to optional_struct"),
     }
 }
 
 struct GlobalOptions {
     new_struct_name: String,
-    extra_derive: Vec<String>,
+    extra_derive: Vec<Path>,
     default_wrapping_behavior: bool,
-    make_fields_public: bool,
+    struct_visibility: Option<Visibility>,
+    field_visibility: Option<Visibility>,
+    extra_bounds: Vec<WherePredicate>,
+    /// The function building the base used by `build_with_defaults`, if any.
+    default_base: Option<Path>,
+    /// The names of all the views generated from the struct, filled once they are all known.
+    view_names: Vec<String>,
+    /// The separator of the names of the variables read by `FromEnv`, if it is implemented.
+    env_separator: Option<String>,
+    /// Whether `clap::Args` is implemented.
+    clap: bool,
 }
 
 impl GlobalOptions {
@@ -584,14 +2904,68 @@ impl GlobalOptions {
             .new_struct_name
             .unwrap_or_else(|| "Optional".to_owned() + &struct_definition.ident.to_string());
         let default_wrapping_behavior = attr.default_wrapping;
+        let extra_derive = attr.derives.unwrap_or_else(|| {
+            // Deriving Default on an enum requires picking a `#[default]` variant
+            if matches!(struct_definition.data, Data::Enum(_)) {
+                vec![
+                    parse_quote!(Clone),
+                    parse_quote!(PartialEq),
+                    parse_quote!(Debug),
+                ]
+            } else {
+                vec![
+                    parse_quote!(Clone),
+                    parse_quote!(PartialEq),
+                    parse_quote!(Default),
+                    parse_quote!(Debug),
+                ]
+            }
+        });
         GlobalOptions {
             new_struct_name,
-            extra_derive: vec!["Clone", "PartialEq", "Default", "Debug"]
-                .into_iter()
-                .map(|s| s.to_owned())
-                .collect(),
+            extra_derive,
             default_wrapping_behavior,
-            make_fields_public: true,
+            struct_visibility: attr.struct_visibility,
+            field_visibility: attr.field_visibility,
+            extra_bounds: attr.extra_bounds,
+            default_base: attr.default_base,
+            view_names: Vec::new(),
+            env_separator: attr.env.then(|| {
+                attr.env_separator
+                    .map_or_else(|| "_".to_owned(), |separator| separator.value())
+            }),
+            clap: attr.clap,
+        }
+    }
+}
+
+/// Whether an item with the visibility `new` is visible wherever an item with the visibility `orig`
+/// is. Restricted visibilities other than `pub(crate)` and `pub(self)` are only comparable when
+/// they are the same.
+fn is_visible_enough(orig: &Visibility, new: &Visibility) -> bool {
+    match (orig, new) {
+        (Visibility::Inherited, _) | (_, Visibility::Public(_)) => true,
+        (Visibility::Restricted(orig), _) if orig.path.is_ident("self") => true,
+        (Visibility::Public(_), _) | (_, Visibility::Inherited) => false,
+        (Visibility::Restricted(orig), Visibility::Restricted(new)) => {
+            new.path.is_ident("crate") || is_same_path(&orig.path, &new.path)
+        }
+    }
+}
+
+fn get_has_optional_impl(orig: &DeriveInput, new: &DeriveInput) -> TokenStream {
+    // The generated struct cannot be exposed through the trait if it is less visible than the
+    // original one, in which case `optional_nested` reports the missing impl (see `HasOptional`).
+    if !is_visible_enough(&orig.vis, &new.vis) {
+        return quote! {};
+    }
+
+    let (impl_generics, ty_generics, where_clause) = new.generics.split_for_impl();
+    let orig_name = &orig.ident;
+    let new_name = &new.ident;
+    quote! {
+        impl #impl_generics optional_struct::HasOptional for #orig_name #ty_generics #where_clause {
+            type Optional = #new_name #ty_generics;
         }
     }
 }
@@ -601,28 +2975,310 @@ pub struct OptionalStructOutput {
     pub generated: TokenStream,
 }
 
-pub fn opt_struct(attr: TokenStream, input: TokenStream) -> OptionalStructOutput {
-    let derive_input = syn::parse2::<DeriveInput>(input).unwrap();
-    let macro_params = GlobalOptions::new(syn::parse2::<_>(attr).unwrap(), &derive_input);
+pub fn opt_struct(attr: TokenStream, input: TokenStream) -> syn::Result<OptionalStructOutput> {
+    let mut derive_input = syn::parse2::<DeriveInput>(input)?;
+    let mut views = vec![syn::parse2::<_>(attr)?];
+
+    // Stacked `#[optional_struct(...)]` attributes are all handled by the first one, each of them
+    // generating another view of the struct.
+    let mut errors = ErrorAccumulator::default();
+    derive_input.attrs.retain(|a| {
+        let is_stacked = a
+            .path()
+            .segments
+            .last()
+            .is_some_and(|s| s.ident == "optional_struct");
+        if is_stacked {
+            views.extend(errors.handle(parse_params_attribute(a)));
+        }
+        !is_stacked
+    });
+    errors.finish()?;
+
+    generate(views, &derive_input)
+}
+
+/// Entry point of `#[derive(OptionalStruct)]`: the parameters are read from the (inert)
+/// `optional_params` attributes, one per view, and only the generated code is returned since the
+/// original struct is left untouched by the compiler.
+pub fn opt_struct_derive(input: TokenStream) -> syn::Result<TokenStream> {
+    let mut derive_input = syn::parse2::<DeriveInput>(input)?;
+    let mut views = Vec::new();
+    let mut errors = ErrorAccumulator::default();
+    for a in derive_input
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident(PARAMS_ATTRIBUTE))
+    {
+        views.extend(errors.handle(parse_params_attribute(a)));
+    }
+    errors.finish()?;
+    if views.is_empty() {
+        views.push(syn::parse2(quote! {})?);
+    }
+
+    // The generated struct must not derive us again
+    derive_input.attrs.retain_mut(|a| {
+        if a.path().is_ident(PARAMS_ATTRIBUTE) {
+            return false;
+        }
+        if !a.path().is_ident("derive") {
+            return true;
+        }
+        let Ok(derives) = a.parse_args_with(Punctuated::<Path, Token![,]>::parse_terminated) else {
+            return true;
+        };
+        let derives = derives
+            .into_iter()
+            .filter(|p| p.segments.last().map_or(true, |s| s.ident != DERIVE_NAME))
+            .collect::<Vec<_>>();
+        *a = parse_quote! { #[derive(#(#derives),*)] };
+        !derives.is_empty()
+    });
+
+    Ok(generate(views, &derive_input)?.generated)
+}
+
+fn parse_params_attribute(attribute: &Attribute) -> syn::Result<ParsedMacroParameters> {
+    match &attribute.meta {
+        Meta::Path(_) => syn::parse2(quote! {}),
+        _ => attribute.parse_args(),
+    }
+}
+
+/// A field of a generated view, used to find out which views can be converted to one another.
+struct ViewField {
+    member: TokenStream,
+    cfg_attribute: TokenStream,
+    ty: Type,
+    /// Whether the field can be left to `None` when missing from the view converted from.
+    is_option: bool,
+}
+
+#[derive(Default)]
+struct CollectViewFields {
+    fields: Vec<ViewField>,
+}
+
+impl OptionalFieldVisitor for CollectViewFields {
+    fn visit(
+        &mut self,
+        _global_options: &GlobalOptions,
+        _old_field: &mut Field,
+        new_field: &mut Field,
+        field_options: &FieldOptions,
+    ) {
+        self.fields.push(ViewField {
+            member: field_options.field_ident.clone(),
+            cfg_attribute: field_options.cfg_attribute.clone(),
+            ty: new_field.ty.clone(),
+            is_option: field_options.wrapping_behavior || field_options.is_base_opt,
+        });
+    }
+}
+
+struct GeneratedView {
+    new: DeriveInput,
+    fields: Vec<ViewField>,
+}
+
+/// Views can be converted to one another when the fields they share have the same type, and the
+/// fields missing from the source are options.
+fn get_view_conversion(from: &GeneratedView, to: &GeneratedView) -> TokenStream {
+    let same_field = |a: &ViewField, b: &ViewField| {
+        a.member.to_string() == b.member.to_string()
+            && a.cfg_attribute.to_string() == b.cfg_attribute.to_string()
+    };
+    let same_type = |a: &Type, b: &Type| quote!(#a).to_string() == quote!(#b).to_string();
+    let mut has_overlap = false;
+    let mut field_assigns = Vec::new();
+    for to_field in &to.fields {
+        let ViewField {
+            member,
+            cfg_attribute,
+            ty,
+            ..
+        } = to_field;
+        match from.fields.iter().find(|f| same_field(f, to_field)) {
+            Some(from_field) if same_type(&from_field.ty, ty) => {
+                has_overlap = true;
+                field_assigns.push(quote! { #cfg_attribute #member: v.#member });
+            }
+            None if to_field.is_option => {
+                field_assigns.push(quote! { #cfg_attribute #member: None });
+            }
+            _ => return quote! {},
+        }
+    }
+    if !has_overlap {
+        return quote! {};
+    }
+
+    let mut generics = to.new.generics.clone();
+    if let Some(from_where_clause) = &from.new.generics.where_clause {
+        generics
+            .make_where_clause()
+            .predicates
+            .extend(from_where_clause.predicates.iter().cloned());
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let from_name = &from.new.ident;
+    let to_name = &to.new.ident;
+    quote! {
+        impl #impl_generics From<#from_name #ty_generics> for #to_name #ty_generics #where_clause {
+            fn from(v: #from_name #ty_generics) -> Self {
+                Self { #(#field_assigns,)* }
+            }
+        }
+    }
+}
+
+fn generate(
+    views: Vec<ParsedMacroParameters>,
+    derive_input: &DeriveInput,
+) -> syn::Result<OptionalStructOutput> {
+    // Every name listed in the positional form generates a view with the same parameters
+    let mut listed_views = Vec::new();
+    for params in views {
+        let others = params
+            .other_names
+            .iter()
+            .map(|name| ParsedMacroParameters {
+                new_struct_name: Some(name.clone()),
+                ..params.clone()
+            })
+            .collect::<Vec<_>>();
+        listed_views.push(params);
+        listed_views.extend(others);
+    }
+    let mut views = listed_views
+        .into_iter()
+        .map(|params| GlobalOptions::new(params, derive_input))
+        .collect::<Vec<_>>();
+    let view_names = views
+        .iter()
+        .map(|v| v.new_struct_name.clone())
+        .collect::<Vec<_>>();
+    if view_names.iter().collect::<HashSet<_>>().len() != view_names.len() {
+        return Err(syn::Error::new(
+            derive_input.ident.span(),
+            "every view generated by optional_struct must have a different name",
+        ));
+    }
+    for view in &mut views {
+        view.view_names = view_names.clone();
+    }
+
+    let mut original = None;
+    let mut generated = quote! {};
+    let mut generated_views = Vec::new();
+    for (i, macro_params) in views.iter().enumerate() {
+        let (orig, view, tokens) = generate_view(macro_params, derive_input, i == 0)?;
+        original.get_or_insert(orig);
+        generated.extend(tokens);
+        generated_views.push(view);
+    }
+
+    // Only structs can leave out fields, so there is no point in converting enums
+    if matches!(derive_input.data, Data::Struct(_)) {
+        for from in &generated_views {
+            for to in &generated_views {
+                if from.new.ident != to.new.ident {
+                    generated.extend(get_view_conversion(from, to));
+                }
+            }
+        }
+    }
+
+    Ok(OptionalStructOutput {
+        original: quote! { #original },
+        generated,
+    })
+}
 
+fn generate_view(
+    macro_params: &GlobalOptions,
+    derive_input: &DeriveInput,
+    is_main_view: bool,
+) -> syn::Result<(DeriveInput, GeneratedView, TokenStream)> {
     let mut applicable_impl_generator = GenerateApplicableImplVisitor::new();
+    let mut reflection_generator = GenerateReflectionVisitor::new(&derive_input.generics);
     let mut try_from_generator = GenerateTryFromImpl::new();
+    let mut view_fields = CollectViewFields::default();
+    let mut build_with_defaults_generator = GenerateBuildWithDefaults::new();
+    let mut diff_generator = GenerateDiffVisitor::new();
+    let mut from_base_generator = GenerateFromBaseVisitor::new();
+    let mut validate_generator = GenerateValidateVisitor::new();
+    let mut from_env_generator = GenerateFromEnvVisitor::new();
+    let mut clap_generator = GenerateClapVisitor::new();
 
     let mut visitors = [
         &mut RemoveHelperAttributesVisitor as &mut dyn OptionalFieldVisitor,
         &mut SetNewFieldVisibilityVisitor,
         &mut SetNewFieldTypeVisitor,
         &mut AddSerdeSkipAttribute,
+        &mut view_fields,
         &mut applicable_impl_generator,
+        &mut reflection_generator,
         &mut try_from_generator,
+        &mut build_with_defaults_generator,
+        &mut diff_generator,
+        &mut from_base_generator,
+        &mut validate_generator,
+        &mut from_env_generator,
+        &mut clap_generator,
     ];
 
-    let (orig, mut new) = visit_fields(&mut visitors, &macro_params, &derive_input);
+    if let (Some(default_base), Data::Enum(_)) = (&macro_params.default_base, &derive_input.data) {
+        return Err(syn::Error::new_spanned(
+            default_base,
+            format!("the `{DEFAULT_PARAMETER}` parameter can only be used on structs"),
+        ));
+    }
+    if let (Some(_), Data::Enum(_)) = (&macro_params.env_separator, &derive_input.data) {
+        return Err(syn::Error::new(
+            derive_input.ident.span(),
+            format!("the `{ENV_PARAMETER}` parameter can only be used on structs"),
+        ));
+    }
+    if let (true, Data::Enum(_)) = (macro_params.clap, &derive_input.data) {
+        return Err(syn::Error::new(
+            derive_input.ident.span(),
+            format!("the `{CLAP_PARAMETER}` parameter can only be used on structs"),
+        ));
+    }
+    let (orig, mut new) = visit_fields(&mut visitors, macro_params, derive_input)?;
 
     new.ident = Ident::new(&macro_params.new_struct_name, new.ident.span());
+    if let Some(vis) = &macro_params.struct_visibility {
+        new.vis = vis.clone();
+    }
+    if !macro_params.extra_bounds.is_empty() {
+        new.generics
+            .make_where_clause()
+            .predicates
+            .extend(macro_params.extra_bounds.iter().cloned());
+    }
 
-    let try_from_impl = try_from_generator.get_implementation(&derive_input, &new);
-    let applicable_impl = applicable_impl_generator.get_implementation(&derive_input, &new);
+    let try_from_impl = try_from_generator.get_implementation(derive_input, &new);
+    // The trait can only point to one of the views
+    let has_optional_impl = if is_main_view {
+        get_has_optional_impl(derive_input, &new)
+    } else {
+        quote! {}
+    };
+    let validate_impl = validate_generator.get_implementation(derive_input, &new, is_main_view);
+    let applicable_impl = applicable_impl_generator.get_implementation(
+        derive_input,
+        &new,
+        reflection_generator.get_implementation(),
+    );
+    let build_with_defaults_impl =
+        build_with_defaults_generator.get_implementation(macro_params, derive_input, &new);
+    let diff_impl = diff_generator.get_implementation(macro_params, derive_input, &new);
+    let from_base_impl = from_base_generator.get_implementation(macro_params, derive_input, &new);
+    let from_env_impl = from_env_generator.get_implementation(macro_params, &new);
+    let clap_impl = clap_generator.get_implementation(macro_params, &new);
 
     let derives = get_derive_macros(&new, &macro_params.extra_derive);
 
@@ -631,10 +3287,18 @@ pub fn opt_struct(attr: TokenStream, input: TokenStream) -> OptionalStructOutput
         #new
         #applicable_impl
         #try_from_impl
+        #has_optional_impl
+        #validate_impl
+        #build_with_defaults_impl
+        #diff_impl
+        #from_base_impl
+        #from_env_impl
+        #clap_impl
     };
 
-    OptionalStructOutput {
-        original: quote! { #orig },
-        generated,
-    }
+    let view = GeneratedView {
+        new,
+        fields: view_fields.fields,
+    };
+    Ok((orig, view, generated))
 }
@@ -1,6 +1,7 @@
+use proc_macro2::TokenStream;
 use quote::quote;
 
-use crate::opt_struct::opt_struct;
+use crate::opt_struct::{opt_struct, opt_struct_derive};
 
 #[test]
 fn basic_gen() {
@@ -12,7 +13,8 @@ fn basic_gen() {
                 baz: String,
             }
         ),
-    );
+    )
+    .unwrap();
 }
 
 #[test]
@@ -25,7 +27,8 @@ fn with_nested() {
                 bar: Bar,
             }
         ),
-    );
+    )
+    .unwrap();
 }
 
 #[test]
@@ -40,7 +43,8 @@ fn with_serde_skip() {
                 bar: Option<Bar>,
             }
         ),
-    );
+    )
+    .unwrap();
 }
 
 #[test]
@@ -54,7 +58,8 @@ fn with_redundant_derive() {
                 baz: String,
             }
         ),
-    );
+    )
+    .unwrap();
 }
 
 #[test]
@@ -68,7 +73,8 @@ fn with_unrelated_derive() {
                 baz: String,
             }
         ),
-    );
+    )
+    .unwrap();
 }
 
 #[test]
@@ -80,7 +86,8 @@ fn with_option() {
                 bar: Option<u8>,
             }
         ),
-    );
+    )
+    .unwrap();
 }
 
 #[test]
@@ -95,7 +102,8 @@ fn with_cfg_attributes() {
                 baz: u8,
             }
         ),
-    );
+    )
+    .unwrap();
 }
 
 #[test]
@@ -123,5 +131,791 @@ fn with_nested_option_skip_wrap() {
                 inner5: Option<Inner>,
             }
         ),
+    )
+    .unwrap();
+}
+
+#[test]
+fn with_enum() {
+    opt_struct(
+        quote!(),
+        quote!(
+            enum Foo {
+                Bar { a: u8, b: Option<u8> },
+                Baz(#[optional_rename(OptionalInner)] Inner),
+                Qux,
+            }
+        ),
+    )
+    .unwrap();
+}
+
+#[test]
+fn error_on_union() {
+    let err = opt_struct(
+        quote!(),
+        quote!(
+            union Foo {
+                bar: u8,
+            }
+        ),
+    )
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("only works for structs and enums"));
+}
+
+#[test]
+fn error_on_unit_struct() {
+    assert!(opt_struct(
+        quote!(),
+        quote!(
+            struct Foo;
+        )
+    )
+    .is_err());
+}
+
+#[test]
+fn error_on_bad_rename() {
+    let err = opt_struct(
+        quote!(),
+        quote!(
+            struct Foo {
+                #[optional_rename(OptionalBar, OptionalBaz)]
+                bar: Bar,
+            }
+        ),
+    )
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("optional_rename"));
+}
+
+#[test]
+fn error_on_unsupported_types_are_all_reported() {
+    let err = opt_struct(
+        quote!(),
+        quote!(
+            struct Foo {
+                bar: my_macro!(),
+                baz: Box<dyn Fn()>,
+                qux: dyn Fn(),
+                #[optional_wrap(true)]
+                quux: u8,
+            }
+        ),
+    )
+    .err()
+    .unwrap();
+    assert_eq!(err.into_iter().count(), 3);
+}
+
+#[test]
+fn with_named_parameters() {
+    opt_struct(
+        quote!(name = Bar, default_wrap = false),
+        quote!(
+            struct Foo {
+                bar: u8,
+            }
+        ),
+    )
+    .unwrap();
+    opt_struct(
+        quote!(Bar, default_wrap = false,),
+        quote!(
+            struct Foo {
+                bar: u8,
+            }
+        ),
+    )
+    .unwrap();
+}
+
+#[test]
+fn error_on_unknown_parameter() {
+    let err = opt_struct(
+        quote!(name = Bar, defualt_wrap = false),
+        quote!(
+            struct Foo {
+                bar: u8,
+            }
+        ),
+    )
+    .err()
+    .unwrap();
+    assert!(err
+        .to_string()
+        .contains("unknown optional_struct parameter `defualt_wrap`"));
+}
+
+#[test]
+fn error_on_duplicate_parameter() {
+    let err = opt_struct(
+        quote!(Bar, name = Baz),
+        quote!(
+            struct Foo {
+                bar: u8,
+            }
+        ),
+    )
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("duplicate"));
+}
+
+#[test]
+fn derives_are_ordered_and_deduplicated() {
+    let out = opt_struct(
+        quote!(),
+        quote!(
+            #[derive(std::fmt::Debug, ::core::clone::Clone, Hash)]
+            struct Foo {
+                bar: u8,
+            }
+        ),
+    )
+    .unwrap();
+    let generated = out.generated.to_string();
+    assert!(generated.contains("# [derive (PartialEq , Default)]"));
+
+    let out = opt_struct(
+        quote!(derive(serde::Serialize, Clone)),
+        quote!(
+            struct Foo {
+                bar: u8,
+            }
+        ),
+    )
+    .unwrap();
+    let generated = out.generated.to_string();
+    assert!(generated.contains("# [derive (serde :: Serialize , Clone)]"));
+}
+
+#[test]
+fn with_visibility() {
+    let out = opt_struct(
+        quote!(vis = pub(crate), field_vis = pub(self)),
+        quote!(
+            pub struct Foo {
+                pub bar: u8,
+            }
+        ),
+    )
+    .unwrap();
+    let generated = out.generated.to_string();
+    assert!(generated.contains("pub (crate) struct OptionalFoo { pub (self) bar"));
+
+    let out = opt_struct(
+        quote!(field_vis = inherit),
+        quote!(
+            struct Foo {
+                pub bar: u8,
+                baz: u8,
+            }
+        ),
+    )
+    .unwrap();
+    let generated = out.generated.to_string();
+    assert!(generated.contains("struct OptionalFoo { pub bar : Option < u8 > , baz"));
+}
+
+#[test]
+fn error_on_bad_visibility() {
+    let err = opt_struct(
+        quote!(field_vis = crate),
+        quote!(
+            struct Foo {
+                bar: u8,
+            }
+        ),
+    )
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("expected a visibility"));
+}
+
+#[test]
+fn has_optional_needs_visible_struct() {
+    let has_optional = |orig: TokenStream, new: TokenStream| {
+        opt_struct(
+            quote!(vis = #new),
+            quote!(
+                #orig struct Foo {
+                    bar: u8,
+                }
+            ),
+        )
+        .unwrap()
+        .generated
+        .to_string()
+        .contains("HasOptional for Foo")
+    };
+    assert!(!has_optional(quote!(pub), quote!(pub(crate))));
+    assert!(!has_optional(quote!(pub(crate)), quote!(pub(super))));
+    assert!(has_optional(quote!(pub(crate)), quote!(pub)));
+    assert!(has_optional(quote!(pub(super)), quote!(pub(crate))));
+    assert!(has_optional(
+        quote!(pub(in crate::a)),
+        quote!(pub(in crate::a))
+    ));
+    assert!(has_optional(quote!(), quote!(pub(self))));
+}
+
+#[test]
+fn option_detection() {
+    let out = opt_struct(
+        quote!(),
+        quote!(
+            struct Foo {
+                a: ::core::option::Option<u8>,
+                b: my::Option<u8>,
+                #[optional_is_option]
+                c: MaybeU8,
+                #[optional_not_option]
+                d: Option<u8>,
+                #[optional_not_option]
+                e: my_macro!(),
+            }
+        ),
+    )
+    .unwrap();
+    let generated = out.generated.to_string();
+    assert!(generated.contains("a : :: core :: option :: Option < u8 > ,"));
+    assert!(generated.contains("b : Option < my :: Option < u8 > > ,"));
+    assert!(generated.contains("c : MaybeU8 ,"));
+    assert!(generated.contains("d : Option < Option < u8 > > ,"));
+    assert!(generated.contains("e : Option < my_macro ! () > ,"));
+}
+
+#[test]
+fn error_on_conflicting_option_overrides() {
+    let err = opt_struct(
+        quote!(),
+        quote!(
+            struct Foo {
+                #[optional_is_option]
+                #[optional_not_option]
+                bar: u8,
+            }
+        ),
+    )
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("mutually exclusive"));
+}
+
+#[test]
+fn with_extra_bounds() {
+    let out = opt_struct(
+        quote!(bound = "T: Clone, U: Default"),
+        quote!(
+            struct Foo<T, U>
+            where
+                T: Debug,
+            {
+                bar: T,
+                baz: U,
+            }
+        ),
+    )
+    .unwrap();
+    let generated = out.generated.to_string();
+    assert!(generated.contains("where T : Debug , T : Clone , U : Default"));
+    assert!(!generated.contains("for OptionalFoo < T , U > {"));
+}
+
+#[test]
+fn attribute_order_is_preserved() {
+    let out = opt_struct(
+        quote!(),
+        quote!(
+            struct Foo {
+                #[doc = "a"]
+                #[optional_wrap]
+                #[doc = "b"]
+                #[optional_skip_attr(allow)]
+                #[allow(unused)]
+                #[doc = "c"]
+                #[optional_attr(doc = "d")]
+                bar: u8,
+            }
+        ),
+    )
+    .unwrap();
+    assert!(out
+        .original
+        .to_string()
+        .contains("# [doc = \"a\"] # [doc = \"b\"] # [allow (unused)] # [doc = \"c\"] bar : u8"));
+    assert!(out.generated.to_string().contains(
+        "# [doc = \"a\"] # [doc = \"b\"] # [doc = \"c\"] # [doc = \"d\"] pub bar : Option < u8 >"
+    ));
+}
+
+#[test]
+fn error_on_rename_and_nested() {
+    let err = opt_struct(
+        quote!(),
+        quote!(
+            struct Foo {
+                #[optional_rename(OptionalBar)]
+                #[optional_nested]
+                bar: Bar,
+            }
+        ),
+    )
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("cannot be used together"));
+}
+
+#[test]
+fn with_renamed_path() {
+    let out = opt_struct(
+        quote!(),
+        quote!(
+            struct Foo<T> {
+                #[optional_rename(crate::bar::OptionalBar<T>)]
+                bar: crate::bar::Bar<T>,
+            }
+        ),
+    )
+    .unwrap();
+    let generated = out.generated.to_string();
+    assert!(generated.contains("pub bar : crate :: bar :: OptionalBar < T > ,"));
+}
+
+#[test]
+fn with_cfg_attr_helper() {
+    let out = opt_struct(
+        quote!(),
+        quote!(
+            struct Foo {
+                #[cfg(a)]
+                #[cfg(b)]
+                #[cfg_attr(c, optional_skip_wrap)]
+                bar: u8,
+            }
+        ),
+    )
+    .unwrap();
+    assert_eq!(
+        out.original.to_string(),
+        "struct Foo { # [cfg (a)] # [cfg (b)] bar : u8 , }"
+    );
+    let generated = out.generated.to_string();
+    assert!(generated.contains(
+        "# [cfg (a)] # [cfg (b)] # [cfg (not (c))] pub bar : Option < u8 > , \
+         # [cfg (a)] # [cfg (b)] # [cfg (c)] pub bar : u8 ,"
+    ));
+}
+
+#[test]
+fn with_derive() {
+    let generated = opt_struct_derive(quote!(
+        #[derive(Clone, optional_struct::OptionalStruct)]
+        #[optional_params(name = Patch, derive(Debug))]
+        struct Foo {
+            #[optional_skip_wrap]
+            bar: u8,
+        }
+    ))
+    .unwrap()
+    .to_string();
+    assert!(generated
+        .starts_with("# [derive (Debug)] # [derive (Clone)] struct Patch { pub bar : u8 , }"));
+}
+
+#[test]
+fn with_views() {
+    let out = opt_struct(
+        quote!(name = Full),
+        quote!(
+            #[optional_struct(name = Partial)]
+            #[optional_struct(name = Unwrapped, default_wrap = false)]
+            struct Foo {
+                bar: u8,
+                #[optional_include(Full, Unwrapped)]
+                baz: u8,
+            }
+        ),
+    )
+    .unwrap();
+    assert_eq!(
+        out.original.to_string(),
+        "struct Foo { bar : u8 , baz : u8 , }"
     );
+    let generated = out.generated.to_string();
+    assert!(generated.contains("struct Partial { pub bar : Option < u8 > , }"));
+    assert!(generated.contains("impl From < Full > for Partial"));
+    assert!(generated.contains("impl From < Partial > for Full"));
+    assert!(!generated.contains("impl From < Unwrapped >"));
+    assert!(!generated.contains("TryFrom < Partial >"));
+    assert!(generated.contains("HasOptional for Foo { type Optional = Full ; }"));
+}
+
+#[test]
+fn error_on_bad_views() {
+    let err = opt_struct(
+        quote!(),
+        quote!(
+            struct Foo {
+                #[optional_exclude(Unknown)]
+                bar: u8,
+            }
+        ),
+    )
+    .err()
+    .unwrap()
+    .into_compile_error()
+    .to_string();
+    assert!(err.contains("unknown view `Unknown`"));
+
+    let err = opt_struct(
+        quote!(),
+        quote!(
+            #[optional_struct]
+            struct Foo {
+                bar: u8,
+            }
+        ),
+    )
+    .err()
+    .unwrap()
+    .into_compile_error()
+    .to_string();
+    assert!(err.contains("every view generated by optional_struct must have a different name"));
+
+    let err = opt_struct(
+        quote!(FooA, FooA),
+        quote!(
+            struct Foo {
+                bar: u8,
+            }
+        ),
+    )
+    .err()
+    .unwrap()
+    .into_compile_error()
+    .to_string();
+    assert!(err.contains("every view generated by optional_struct must have a different name"));
+
+    let err = opt_struct(
+        quote!(),
+        quote!(
+            enum Foo {
+                A {
+                    #[optional_exclude(Unknown)]
+                    bar: u8,
+                },
+            }
+        ),
+    )
+    .err()
+    .unwrap()
+    .into_compile_error()
+    .to_string();
+    assert!(err.contains("views can only leave out named fields of structs"));
+    assert!(err.contains("unknown view `Unknown`"));
+}
+
+#[test]
+fn error_on_bad_defaults() {
+    let err = opt_struct(
+        quote!(default = Foo::base),
+        quote!(
+            enum Foo {
+                A { bar: u8 },
+            }
+        ),
+    )
+    .err()
+    .unwrap();
+    assert!(err
+        .to_string()
+        .contains("the `default` parameter can only be used on structs"));
+
+    let err = opt_struct(
+        quote!(),
+        quote!(
+            struct Foo {
+                #[optional_default()]
+                bar: u8,
+            }
+        ),
+    )
+    .err()
+    .unwrap();
+    assert!(err
+        .to_string()
+        .contains("'optional_default' expects one expression"));
+
+    let err = opt_struct(
+        quote!(),
+        quote!(
+            struct Foo {
+                #[optional_skip_wrap]
+                #[optional_default(1)]
+                bar: u8,
+            }
+        ),
+    )
+    .err()
+    .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "'optional_default' cannot be used on fields that are always set, unless they are merged with 'optional_merge'"
+    );
+
+    opt_struct(
+        quote!(),
+        quote!(
+            struct Foo {
+                #[optional_skip_wrap]
+                #[optional_merge(sum)]
+                #[optional_default(1)]
+                bar: u8,
+            }
+        ),
+    )
+    .unwrap();
+}
+
+#[test]
+fn diff_requires_clone_and_partial_eq() {
+    let diff_impl = "optional_struct :: Diff for OptionalFoo";
+    let with = |attr| {
+        opt_struct(
+            attr,
+            quote!(
+                struct Foo {
+                    bar: u8,
+                }
+            ),
+        )
+        .unwrap()
+        .generated
+        .to_string()
+    };
+    assert!(with(quote!()).contains(diff_impl));
+    assert!(with(quote!(derive(Clone, core::cmp::PartialEq))).contains(diff_impl));
+    assert!(!with(quote!(derive(Clone, Debug))).contains(diff_impl));
+
+    // Merged values cannot be diffed
+    let generated = opt_struct(
+        quote!(),
+        quote!(
+            struct Foo {
+                #[optional_merge(max)]
+                bar: u8,
+            }
+        ),
+    )
+    .unwrap()
+    .generated
+    .to_string();
+    assert!(!generated.contains(diff_impl));
+}
+
+#[test]
+fn error_on_bad_merge_strategies() {
+    let err = opt_struct(
+        quote!(),
+        quote!(
+            struct Foo {
+                #[optional_merge(concat)]
+                bar: Vec<u8>,
+            }
+        ),
+    )
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("unknown merge strategy `concat`"));
+
+    let err = opt_struct(
+        quote!(),
+        quote!(
+            struct Foo {
+                #[optional_nested]
+                #[optional_merge(append)]
+                bar: Bar,
+            }
+        ),
+    )
+    .err()
+    .unwrap();
+    assert!(err
+        .to_string()
+        .contains("'optional_merge' cannot be used on nested or renamed fields"));
+
+    let output = opt_struct(
+        quote!(),
+        quote!(
+            struct Foo {
+                #[optional_merge(with = my::merge)]
+                bar: u8,
+            }
+        ),
+    )
+    .unwrap()
+    .generated
+    .to_string();
+    assert!(output.contains("my :: merge"));
+    assert!(!output.contains("optional_struct :: Diff for OptionalFoo"));
+}
+
+#[test]
+fn error_on_bad_validations() {
+    let err = opt_struct(
+        quote!(),
+        quote!(
+            struct Foo {
+                #[optional_validate(positive)]
+                bar: u8,
+            }
+        ),
+    )
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("unknown validation `positive`"));
+
+    let err = opt_struct(
+        quote!(),
+        quote!(
+            enum Foo {
+                A {
+                    #[optional_validate(non_empty)]
+                    bar: String,
+                },
+            }
+        ),
+    )
+    .err()
+    .unwrap();
+    assert!(err
+        .to_string()
+        .contains("'optional_validate' can only be used on fields of structs"));
+
+    let output = opt_struct(
+        quote!(),
+        quote!(
+            struct Foo {
+                #[optional_validate(range(1..=10), with = my::check)]
+                bar: u8,
+            }
+        ),
+    )
+    .unwrap()
+    .generated
+    .to_string();
+    assert!(output.contains("optional_struct :: validate :: range (value , 1 ..= 10)"));
+    assert!(output.contains("my :: check (value)"));
+}
+
+#[test]
+fn error_on_bad_env_parameters() {
+    let err = opt_struct(
+        quote!(env_separator = "__"),
+        quote!(
+            struct Foo {
+                bar: u8,
+            }
+        ),
+    )
+    .err()
+    .unwrap();
+    assert!(err
+        .to_string()
+        .contains("the `env_separator` parameter requires `env = true`"));
+
+    let err = opt_struct(
+        quote!(env = true),
+        quote!(
+            enum Foo {
+                A { bar: u8 },
+            }
+        ),
+    )
+    .err()
+    .unwrap();
+    assert!(err
+        .to_string()
+        .contains("the `env` parameter can only be used on structs"));
+
+    let err = opt_struct(
+        quote!(env = true),
+        quote!(
+            struct Foo {
+                #[optional_env(BAR)]
+                bar: u8,
+            }
+        ),
+    )
+    .err()
+    .unwrap();
+    assert!(err
+        .to_string()
+        .contains("'optional_env' expects the name of the variable, or `skip`"));
+
+    let without_env = opt_struct(
+        quote!(),
+        quote!(
+            struct Foo {
+                bar: u8,
+            }
+        ),
+    )
+    .unwrap()
+    .generated
+    .to_string();
+    assert!(!without_env.contains("FromEnv"));
+}
+
+#[test]
+fn error_on_bad_clap_options() {
+    let err = opt_struct(
+        quote!(clap = true),
+        quote!(
+            enum Foo {
+                A { bar: u8 },
+            }
+        ),
+    )
+    .err()
+    .unwrap();
+    assert!(err
+        .to_string()
+        .contains("the `clap` parameter can only be used on structs"));
+
+    let err = opt_struct(
+        quote!(clap = true),
+        quote!(
+            struct Foo {
+                #[optional_clap(short)]
+                bar: u8,
+            }
+        ),
+    )
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("expected `=`"));
+
+    let output = opt_struct(
+        quote!(clap = true),
+        quote!(
+            struct Foo {
+                #[optional_nested]
+                #[optional_clap(short = 'b')]
+                bar: Bar,
+                #[optional_clap(short = 'b', help = "Baz")]
+                baz: u8,
+            }
+        ),
+    )
+    .unwrap()
+    .generated
+    .to_string();
+    assert!(output.contains("'optional_clap' can only skip nested fields"));
+    assert!(output.contains(". short ('b') . help (\"Baz\")"));
 }
@@ -0,0 +1,26 @@
+//! Parsing optional_struct from the command line with clap, see `ClapArgs`.
+
+use clap::{ArgMatches, Command};
+
+/// Implemented by the structures generated with `#[optional_struct(clap = true)]`, which also
+/// implement `clap::Args`, so they can be flattened in a parser:
+/// ```ignore
+/// #[derive(clap::Parser)]
+/// struct Cli {
+///     #[command(flatten)]
+///     config: OptionalConfig,
+/// }
+/// ```
+/// Every field is an optional `--flag` named after it (e.g. `--log-file`, repeated for `Vec`s), and
+/// nested structures are flattened, their flags being prefixed with the name of the field (e.g.
+/// `--log-config-log-file`).
+pub trait ClapArgs: Sized {
+    /// Adds the arguments of the fields, whose names start with `prefix` (e.g. `log-config-`).
+    fn augment_args_prefixed(cmd: Command, prefix: &str) -> Command;
+
+    /// Reads the arguments added by `ClapArgs::augment_args_prefixed` with the same `prefix`.
+    fn from_arg_matches_prefixed(
+        matches: &mut ArgMatches,
+        prefix: &str,
+    ) -> Result<Self, clap::Error>;
+}
@@ -0,0 +1,110 @@
+//! Reading optional_struct from environment variables, see `FromEnv`.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use core::borrow::Borrow;
+use core::fmt;
+
+/// Implemented by the structures generated with `#[optional_struct(env = true)]`.
+/// The fields are read from the variables named `{prefix}{separator}{FIELD}` (e.g.
+/// `APP_LOG_CONFIG_LOG_FILE` for a nested field), or as given with `#[optional_env("NAME")]`, and
+/// parsed with `FromStr`. Fields without a variable are left unset.
+pub trait FromEnv: Sized {
+    /// Reads the variables from `source` instead of the environment of the process, e.g. a
+    /// `BTreeMap` in tests.
+    fn from_env_with<S: EnvSource + ?Sized>(prefix: &str, source: &S) -> Result<Self, EnvError>;
+
+    /// Reads the variables from the environment of the process, e.g. with the prefix `APP`.
+    #[cfg(feature = "std")]
+    fn from_env(prefix: &str) -> Result<Self, EnvError> {
+        Self::from_env_with(prefix, &ProcessEnv)
+    }
+}
+
+/// Where `FromEnv` reads the variables from.
+pub trait EnvSource {
+    /// The value of the variable, or `None` if it is not set.
+    fn get(&self, name: &str) -> Option<String>;
+}
+
+impl<T: EnvSource + ?Sized> EnvSource for &T {
+    fn get(&self, name: &str) -> Option<String> {
+        (**self).get(name)
+    }
+}
+
+impl<K: Borrow<str> + Ord, V: AsRef<str>> EnvSource for BTreeMap<K, V> {
+    fn get(&self, name: &str) -> Option<String> {
+        BTreeMap::get(self, name).map(|v| String::from(v.as_ref()))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S> EnvSource for std::collections::HashMap<K, V, S>
+where
+    K: Borrow<str> + core::hash::Hash + Eq,
+    V: AsRef<str>,
+    S: core::hash::BuildHasher,
+{
+    fn get(&self, name: &str) -> Option<String> {
+        std::collections::HashMap::get(self, name).map(|v| String::from(v.as_ref()))
+    }
+}
+
+/// The environment of the process. Values that are not valid unicode are converted lossily.
+#[cfg(feature = "std")]
+pub struct ProcessEnv;
+
+#[cfg(feature = "std")]
+impl EnvSource for ProcessEnv {
+    fn get(&self, name: &str) -> Option<String> {
+        std::env::var_os(name).map(|v| v.to_string_lossy().into_owned())
+    }
+}
+
+/// A variable whose value could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvError {
+    variable: String,
+    value: String,
+    message: String,
+}
+
+impl EnvError {
+    /// An error for `value`, read from `variable`, with the `message` of the parsing error.
+    pub fn new(variable: String, value: String, message: String) -> Self {
+        EnvError {
+            variable,
+            value,
+            message,
+        }
+    }
+
+    /// The name of the variable, e.g. `APP_PORT`.
+    pub fn variable(&self) -> &str {
+        &self.variable
+    }
+
+    /// The value that could not be parsed.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// The error returned by `FromStr`.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid value {:?} for the environment variable `{}`: {}",
+            self.value, self.variable, self.message
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EnvError {}
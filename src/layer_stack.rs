@@ -0,0 +1,136 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{Applicable, HasOptional, MissingFields, TrackedPatch};
+
+struct NamedLayer<P> {
+    name: String,
+    priority: i32,
+    patch: P,
+}
+
+/// Named layers (e.g. `"defaults"`, `"file"`, `"env"`, `"cli"`), each with a priority, which can be
+/// resolved to the Base (or to the optional_struct) at any time. Layers with a higher priority
+/// take precedence over the ones with a lower priority, and layers with the same priority are
+/// applied in the order they were inserted.
+/// Layers can be given as anything convertible to the optional_struct of the Base, e.g. another
+/// view of it, or the Base itself.
+pub struct LayerStack<B: HasOptional> {
+    /// Sorted by priority, from the lowest to the highest.
+    layers: Vec<NamedLayer<B::Optional>>,
+}
+
+impl<B: HasOptional> Default for LayerStack<B> {
+    fn default() -> Self {
+        LayerStack { layers: Vec::new() }
+    }
+}
+
+impl<B: HasOptional> LayerStack<B> {
+    /// An empty stack, with no layers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts a layer, returning the previous layer with the same name, if any (which is
+    /// removed, even if its priority is different).
+    pub fn insert(
+        &mut self,
+        name: &str,
+        priority: i32,
+        layer: impl Into<B::Optional>,
+    ) -> Option<B::Optional> {
+        let previous = self.remove(name);
+        let index = self
+            .layers
+            .iter()
+            .position(|l| l.priority > priority)
+            .unwrap_or(self.layers.len());
+        self.layers.insert(
+            index,
+            NamedLayer {
+                name: String::from(name),
+                priority,
+                patch: layer.into(),
+            },
+        );
+        previous
+    }
+
+    /// Replaces the content of an existing layer, keeping its priority, and returns the previous
+    /// content. Nothing is inserted if there is no layer with that name.
+    pub fn replace(&mut self, name: &str, layer: impl Into<B::Optional>) -> Option<B::Optional> {
+        let existing = self.layers.iter_mut().find(|l| l.name == name)?;
+        Some(core::mem::replace(&mut existing.patch, layer.into()))
+    }
+
+    /// Removes the layer with this name, returning its content.
+    pub fn remove(&mut self, name: &str) -> Option<B::Optional> {
+        let index = self.layers.iter().position(|l| l.name == name)?;
+        Some(self.layers.remove(index).patch)
+    }
+
+    /// The content of the layer with this name.
+    pub fn get(&self, name: &str) -> Option<&B::Optional> {
+        self.layers
+            .iter()
+            .find(|l| l.name == name)
+            .map(|l| &l.patch)
+    }
+
+    /// The priority of the layer with this name.
+    pub fn priority(&self, name: &str) -> Option<i32> {
+        self.layers
+            .iter()
+            .find(|l| l.name == name)
+            .map(|l| l.priority)
+    }
+
+    /// The names of the layers, from the lowest priority to the highest.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.layers.iter().map(|l| l.name.as_str())
+    }
+
+    /// The number of layers.
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Whether the stack has no layers.
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+}
+
+impl<B: HasOptional> LayerStack<B>
+where
+    B::Optional: Applicable<Base = B> + Clone + Default,
+{
+    /// Merges all the layers into a single optional_struct.
+    pub fn resolve_patch(&self) -> B::Optional {
+        self.layers
+            .iter()
+            .fold(B::Optional::default(), |acc, l| acc.apply(l.patch.clone()))
+    }
+
+    /// Applies all the layers to `base`.
+    pub fn resolve(&self, base: B) -> B {
+        self.resolve_patch().build(base)
+    }
+
+    /// Builds the Base from the layers only, failing if some fields are set by none of them.
+    pub fn try_resolve(&self) -> Result<B, MissingFields<B::Optional>>
+    where
+        B: TryFrom<B::Optional, Error = MissingFields<B::Optional>>,
+    {
+        B::try_from(self.resolve_patch())
+    }
+
+    /// Similar to `LayerStack::resolve_patch`, but remembers which layer set every field, the
+    /// sources being the names of the layers.
+    pub fn resolve_tracked(&self) -> TrackedPatch<B::Optional> {
+        self.layers.iter().fold(TrackedPatch::default(), |acc, l| {
+            acc.apply(&l.name, l.patch.clone())
+        })
+    }
+}
@@ -5,8 +5,32 @@
 //! simplifying aggregating configurations coming from different sources, such as e.g. file, env,
 //! CLI, etc.
 
-/// The core of this crate. Call this proc macro on your structures to generate another structure
-/// containing `Option`al fields, as well as helpers functions to convert those optional_struct to
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "clap")]
+mod cli;
+pub mod env;
+mod layer_stack;
+pub mod merge;
+mod provenance;
+pub mod validate;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::fmt;
+
+#[cfg(feature = "clap")]
+pub use cli::ClapArgs;
+pub use env::{EnvError, EnvSource, FromEnv};
+pub use layer_stack::LayerStack;
+pub use provenance::{ProvenanceReport, TrackedPatch};
+pub use validate::{Validate, ValidationError, ValidationErrors};
+
+/// The core of this crate. Call this proc macro on your structures (or enums) to generate another
+/// structure containing `Option`al fields, as well as helpers functions to convert those optional_struct to
 /// their base, or even update only fields that have been set. This makes aggregating structures
 /// from different sources (e.g. configuration from file/env/CLI) simple.
 /// The generated struct by default will wrap all fields in an `Option`, unless the field already
@@ -15,14 +39,43 @@
 /// optional_rename => rename the type in the generated structure. Useful when the nested structure
 /// itself has an optional_struct. This enables arbitrary nesting of optional_struct (see tests for
 /// examples).
+/// optional_nested => same as optional_rename, but the generated type is found through the
+/// `HasOptional` trait, so there is no need to know its name.
 /// optional_skip_wrap => this force *not* wrapping a value, e.g. `T` stays `T`. This is enabled by
 /// default if `T` is a `Optiona<U>`.
 /// optional_wrap => this forces wrapping a value, e.g. `U` becomes `Option<U>`. Enabling this
 /// allows nested `Option`, e.g. `Option<V>` can become `Option<Option<V>>`
+/// optional_is_option / optional_not_option => only `Option`, `std::option::Option` and
+/// `core::option::Option` are recognized as options. Use those to tell otherwise, e.g. for type
+/// aliases.
 /// optional_serde_skip_none => This generate an extra `#[serde(skip_serializing_if = ... )]` to the
 /// generated structures. Useful if you want to (de)serialize those structures with serde.
+/// optional_attr / optional_skip_attr => respectively add an attribute only to the generated
+/// structure (or field), or keep the attributes with the given path off it.
+/// optional_include / optional_exclude => when stacking several `optional_struct` attributes (or
+/// listing several names, e.g. `#[optional_struct(ConfigFileLayer, CliLayer)]`) to generate several
+/// views of a structure, respectively keep a field only in the given views, or leave it out of
+/// them.
+/// optional_default => the value used by the generated `build_with_defaults` when the field is not
+/// set. Without it, the struct-level `default` function, or `Default::default()`, is used instead.
+/// optional_merge => how a set value is merged with the existing one instead of replacing it, with
+/// one of the strategies of the `merge` module (e.g. `#[optional_merge(append)]`), or
+/// `#[optional_merge(with = path::to::function)]`.
+/// optional_validate => checks run on the field by the generated `Validate` impls (of the base, and
+/// of the generated struct when the field is set) and `try_build`, with the functions of the
+/// `validate` module (e.g. `#[optional_validate(range(1..=65535), non_empty)]`), or
+/// `#[optional_validate(with = path::to::function)]`.
+/// optional_env => with `#[optional_struct(env = true)]`, the name of the environment variable the
+/// field is read from by `FromEnv` (e.g. `#[optional_env("DATABASE_URL")]`), or `skip`.
+/// optional_clap => with `#[optional_struct(clap = true)]` (and the `clap` feature), calls to the
+/// methods of the `clap::Arg` of the field (e.g. `#[optional_clap(short = 'p', alias = "listen")]`),
+/// or `skip`.
 pub use optional_struct_macro::optional_struct;
 
+/// Derive version of `optional_struct`, generating the same code. The helper attributes are the
+/// same, and the parameters of the macro are given with `#[optional_params(...)]` instead.
+pub use optional_struct_macro::OptionalStruct;
+
 /// The trait is implemented for every generated structure. Thanks to this, you can use
 /// optional_struct in generic contexts.
 /// You should never have to implement this manually.
@@ -31,6 +84,9 @@ pub trait Applicable: Sized {
     /// generate methods generating such structures.
     type Base;
 
+    /// The names of the fields of this structure (of all its variants, for enums).
+    const FIELD_NAMES: &'static [&'static str];
+
     /// This function applies all the fields set in this structure to an instance of its Base.
     /// Note that this works recursively, enabling the use of nested optional_struct structures.
     fn build(self, mut base: Self::Base) -> Self::Base {
@@ -50,13 +106,490 @@ pub trait Applicable: Sized {
     fn apply_to_opt(self, other: &mut Self);
 
     /// Similar to `apply_to_opt` but the argument `other` is applied to self. This allows chaining
-    /// calls.
-    fn apply(mut self, other: Self) -> Self {
-        other.apply_to_opt(&mut self);
+    /// calls. `other` can also be anything convertible to this structure, e.g. a whole Base.
+    fn apply(mut self, other: impl Into<Self>) -> Self {
+        other.into().apply_to_opt(&mut self);
         self
     }
 
     /// Signals whether the optional_struct has all its fields set to convert it to a Base.
     /// i.e. self.can_convert() == Base::try_from(self).is_ok()
     fn can_convert(&self) -> bool;
+
+    /// The dotted paths (e.g. `log_config.log_file`) of the fields preventing the conversion to a
+    /// Base, including those of nested structures.
+    /// i.e. self.missing_fields().is_empty() == self.can_convert()
+    fn missing_fields(&self) -> Vec<String>;
+
+    /// Calls the visitor on every field of this structure. The fields of nested structures are
+    /// visited instead of the nested structure itself (unless it is not set).
+    fn visit(&self, visitor: &mut dyn PatchVisitor) {
+        self.visit_with_prefix("", visitor);
+    }
+
+    /// Similar to `Applicable::visit`, but the paths of the fields start with `prefix`. This is
+    /// what nested structures are visited with.
+    fn visit_with_prefix(&self, prefix: &str, visitor: &mut dyn PatchVisitor);
+
+    /// The number of fields that are set, including those of nested structures.
+    fn count_set(&self) -> usize {
+        let mut count = 0;
+        self.visit(&mut |field: &PatchField<'_>| {
+            if field.is_set {
+                count += 1;
+            }
+        });
+        count
+    }
+
+    /// Whether no field at all is set.
+    fn is_empty(&self) -> bool {
+        self.count_set() == 0
+    }
+}
+
+/// Computes the patch turning a Base into another. This is implemented for generated structures
+/// deriving `Clone` and `PartialEq` (which they do by default), not leaving out any field, and
+/// without `optional_merge` fields (whose new value cannot be set by a patch in general, e.g. a
+/// smaller value with `max`).
+/// You should never have to implement this manually.
+pub trait Diff: Applicable {
+    /// The patch setting only the fields that differ between `old` and `new` (recursing into
+    /// nested structures), i.e. `Self::diff(&old, &new).build(old) == new`.
+    /// `Option`s of the Base that are not wrapped cannot be reset to `None` by a patch, so such
+    /// changes are left out. This includes nested `Option`al structures, which are otherwise
+    /// diffed when they are set on both sides, or set entirely when only set in `new` (unless
+    /// they are wrapped, in which case a patch cannot set them either).
+    fn diff(old: &Self::Base, new: &Self::Base) -> Self;
+}
+
+/// A field of an optional_struct, as seen by a `PatchVisitor`.
+pub struct PatchField<'a> {
+    /// The name of the field, e.g. `log_file` (or `0` for tuple structures).
+    pub name: &'static str,
+    /// The dotted path of the field from the visited structure, e.g. `log_config.log_file`.
+    pub path: &'a str,
+    /// Whether the field is set. Fields that are not wrapped in an `Option` are always set.
+    pub is_set: bool,
+    /// The value of the field (i.e. including the `Option` wrapping it), if its type implements
+    /// `Debug`.
+    pub debug: Option<&'a dyn fmt::Debug>,
+    /// The value of the field, if its type is `'static` (types refering to lifetimes or generic
+    /// parameters of the structure are never considered as such).
+    pub any: Option<&'a dyn Any>,
+}
+
+/// Visits the fields of an optional_struct, see `Applicable::visit`.
+pub trait PatchVisitor {
+    fn visit_field(&mut self, field: &PatchField<'_>);
+}
+
+impl<F: FnMut(&PatchField<'_>)> PatchVisitor for F {
+    fn visit_field(&mut self, field: &PatchField<'_>) {
+        self(field)
+    }
+}
+
+/// The error returned when converting an optional_struct to its Base while some fields are not
+/// set. The optional_struct can be recovered with `MissingFields::into_patch`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingFields<T> {
+    patch: T,
+    fields: Vec<String>,
+}
+
+impl<T> MissingFields<T> {
+    /// An error for `patch`, whose `fields` are not set.
+    pub fn new(patch: T, fields: Vec<String>) -> Self {
+        MissingFields { patch, fields }
+    }
+
+    /// The dotted paths of the fields that are not set, see `Applicable::missing_fields`.
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    /// The optional_struct that could not be converted.
+    pub fn patch(&self) -> &T {
+        &self.patch
+    }
+
+    /// Gives back the optional_struct, e.g. to apply the missing fields to it and try again.
+    pub fn into_patch(self) -> T {
+        self.patch
+    }
+}
+
+impl<T> fmt::Display for MissingFields<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("missing fields: ")?;
+        for (i, field) in self.fields.iter().enumerate() {
+            if i != 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "`{field}`")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: fmt::Debug> std::error::Error for MissingFields<T> {}
+
+/// Used by the generated code, which cannot rely on `alloc` being available in the user's crate.
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::string::String;
+    pub use alloc::vec::Vec;
+    #[cfg(feature = "clap")]
+    pub use clap;
+
+    pub use crate::__optional_struct_clap_impls as clap_impls;
+
+    /// Wraps the implementations generated with `#[optional_struct(clap = true)]`, which need the
+    /// `clap` feature.
+    #[cfg(feature = "clap")]
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __optional_struct_clap_impls {
+        ($($impls:tt)*) => {
+            $($impls)*
+        };
+    }
+
+    #[cfg(not(feature = "clap"))]
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __optional_struct_clap_impls {
+        ($($impls:tt)*) => {
+            compile_error!(
+                "`#[optional_struct(clap = true)]` requires the `clap` feature of optional_struct"
+            );
+        };
+    }
+
+    use core::any::Any;
+    use core::fmt::Debug;
+
+    pub fn field_path(prefix: &str, name: &str) -> String {
+        alloc::format!("{prefix}{name}")
+    }
+
+    pub fn nested_prefix(prefix: &str, name: &str) -> String {
+        alloc::format!("{prefix}{name}.")
+    }
+
+    /// Merges optional values with one of the `merge` strategies: a value is only set when there
+    /// was none, and `None` leaves the existing value untouched.
+    pub fn merge_option<T>(
+        existing: &mut Option<T>,
+        new: Option<T>,
+        merge: impl FnOnce(&mut T, T),
+    ) {
+        match (existing, new) {
+            (Some(existing), Some(new)) => merge(existing, new),
+            (existing, Some(new)) => *existing = Some(new),
+            (_, None) => {}
+        }
+    }
+
+    /// Autoref specialization, so that `(&&Reflected(v)).reflect_debug()` is `Some` iff `v`
+    /// implements `Debug` (and similarly for `Any`).
+    pub struct Reflected<'a, T: ?Sized>(pub &'a T);
+
+    pub trait ReflectDebug<'a> {
+        fn reflect_debug(&self) -> Option<&'a dyn Debug>;
+    }
+
+    impl<'a, T: Debug> ReflectDebug<'a> for &Reflected<'a, T> {
+        fn reflect_debug(&self) -> Option<&'a dyn Debug> {
+            Some(self.0)
+        }
+    }
+
+    pub trait ReflectDebugFallback<'a> {
+        fn reflect_debug(&self) -> Option<&'a dyn Debug>;
+    }
+
+    impl<'a, T: ?Sized> ReflectDebugFallback<'a> for Reflected<'a, T> {
+        fn reflect_debug(&self) -> Option<&'a dyn Debug> {
+            None
+        }
+    }
+
+    pub trait ReflectAny<'a> {
+        fn reflect_any(&self) -> Option<&'a dyn Any>;
+    }
+
+    impl<'a, T: Any> ReflectAny<'a> for &Reflected<'a, T> {
+        fn reflect_any(&self) -> Option<&'a dyn Any> {
+            Some(self.0)
+        }
+    }
+
+    pub trait ReflectAnyFallback<'a> {
+        fn reflect_any(&self) -> Option<&'a dyn Any>;
+    }
+
+    impl<'a, T: ?Sized> ReflectAnyFallback<'a> for Reflected<'a, T> {
+        fn reflect_any(&self) -> Option<&'a dyn Any> {
+            None
+        }
+    }
+
+    pub fn push_validation_error<E: core::fmt::Display>(
+        errors: &mut Vec<crate::ValidationError>,
+        prefix: &str,
+        name: &str,
+        result: Result<(), E>,
+    ) {
+        if let Err(e) = result {
+            errors.push(crate::ValidationError::new(
+                field_path(prefix, name),
+                alloc::format!("{e}"),
+            ));
+        }
+    }
+
+    /// The name of the variable of a field (or the prefix of a nested structure) read by
+    /// `FromEnv`.
+    pub fn env_name(prefix: &str, separator: &str, name: &str) -> String {
+        if prefix.is_empty() {
+            String::from(name)
+        } else {
+            alloc::format!("{prefix}{separator}{name}")
+        }
+    }
+
+    /// Remembers whether any variable was found in the source, to tell whether a wrapped nested
+    /// structure is set.
+    pub struct EnvReadTracker<'a, S: ?Sized> {
+        source: &'a S,
+        was_read: core::cell::Cell<bool>,
+    }
+
+    impl<'a, S: ?Sized> EnvReadTracker<'a, S> {
+        pub fn new(source: &'a S) -> Self {
+            EnvReadTracker {
+                source,
+                was_read: core::cell::Cell::new(false),
+            }
+        }
+
+        pub fn was_read(&self) -> bool {
+            self.was_read.get()
+        }
+    }
+
+    impl<S: crate::EnvSource + ?Sized> crate::EnvSource for EnvReadTracker<'_, S> {
+        fn get(&self, name: &str) -> Option<String> {
+            let value = self.source.get(name);
+            if value.is_some() {
+                self.was_read.set(true);
+            }
+            value
+        }
+    }
+
+    pub fn parse_env<T, S>(source: &S, name: &str) -> Result<Option<T>, crate::EnvError>
+    where
+        T: core::str::FromStr,
+        T::Err: core::fmt::Display,
+        S: crate::EnvSource + ?Sized,
+    {
+        let Some(value) = source.get(name) else {
+            return Ok(None);
+        };
+        match value.parse() {
+            Ok(parsed) => Ok(Some(parsed)),
+            Err(e) => Err(crate::EnvError::new(
+                String::from(name),
+                value,
+                alloc::format!("{e}"),
+            )),
+        }
+    }
+
+    /// The argument of a field, e.g. `--log-config-log-file`, also used as its id.
+    #[cfg(feature = "clap")]
+    pub fn clap_arg(prefix: &str, name: &str, help: &str) -> clap::Arg {
+        let id = alloc::format!("{prefix}{name}");
+        let arg = clap::Arg::new(id.clone())
+            .long(id)
+            .action(clap::ArgAction::Set)
+            .required(false);
+        if help.is_empty() {
+            arg
+        } else {
+            arg.help(String::from(help))
+        }
+    }
+
+    #[cfg(feature = "clap")]
+    pub fn nested_clap_prefix(prefix: &str, name: &str) -> String {
+        alloc::format!("{prefix}{name}-")
+    }
+
+    /// Adds the arguments of a nested structure with `augment`, along with a group named `group`
+    /// holding exactly these arguments, see `clap_group_is_given`.
+    #[cfg(feature = "clap")]
+    pub fn clap_nested_group(
+        cmd: clap::Command,
+        group: &str,
+        augment: impl FnOnce(clap::Command) -> clap::Command,
+    ) -> clap::Command {
+        let existing = cmd
+            .get_arguments()
+            .map(|a| a.get_id().clone())
+            .collect::<Vec<_>>();
+        let cmd = augment(cmd);
+        let added = cmd
+            .get_arguments()
+            .map(|a| a.get_id().clone())
+            .filter(|id| !existing.contains(id))
+            .collect::<Vec<_>>();
+        cmd.group(
+            clap::ArgGroup::new(String::from(group))
+                .args(added)
+                .multiple(true)
+                .required(false),
+        )
+    }
+
+    /// Whether one of the arguments of the group was given (i.e. not taken from its default value).
+    #[cfg(feature = "clap")]
+    pub fn clap_group_is_given(matches: &clap::ArgMatches, group: &str) -> bool {
+        let Ok(Some(ids)) = matches.try_get_many::<clap::Id>(group) else {
+            return false;
+        };
+        ids.into_iter().any(|id| {
+            matches
+                .value_source(id.as_str())
+                .is_some_and(|s| s != clap::parser::ValueSource::DefaultValue)
+        })
+    }
+
+    /// Similar to `clap_arg`, but `--flag` alone means `--flag true`.
+    #[cfg(feature = "clap")]
+    pub fn clap_flag(prefix: &str, name: &str, help: &str) -> clap::Arg {
+        clap_arg(prefix, name, help)
+            .num_args(0..=1)
+            .default_missing_value("true")
+    }
+
+    #[cfg(feature = "clap")]
+    pub fn clap_value<T: Clone + Send + Sync + 'static>(
+        matches: &mut clap::ArgMatches,
+        prefix: &str,
+        name: &str,
+    ) -> Option<T> {
+        matches.remove_one::<T>(&alloc::format!("{prefix}{name}"))
+    }
+
+    /// Similar to `clap_value`, for the arguments that can be repeated.
+    #[cfg(feature = "clap")]
+    pub fn clap_values<T: Clone + Send + Sync + 'static>(
+        matches: &mut clap::ArgMatches,
+        prefix: &str,
+        name: &str,
+    ) -> Option<Vec<T>> {
+        matches
+            .remove_many::<T>(&alloc::format!("{prefix}{name}"))
+            .map(Iterator::collect)
+    }
+
+    /// Used by the generated `try_build`: when some fields are missing, the base cannot be built,
+    /// so they are reported first, followed by the fields of the patch that are set but not valid.
+    pub fn try_build<P>(
+        patch: P,
+        validate_base: impl FnOnce(&P::Base) -> Vec<crate::ValidationError>,
+    ) -> Result<P::Base, crate::ValidationErrors>
+    where
+        P: crate::Applicable + crate::Validate,
+        P::Base: TryFrom<P>,
+    {
+        let missing = patch.missing_fields();
+        let invalid = if missing.is_empty() {
+            Vec::new()
+        } else {
+            crate::Validate::validate(&patch)
+        };
+        let base = match P::Base::try_from(patch) {
+            Ok(base) => base,
+            Err(_) => {
+                let errors = missing
+                    .into_iter()
+                    .map(|path| crate::ValidationError::new(path, String::from("is not set")))
+                    .chain(invalid)
+                    .collect();
+                return Err(crate::ValidationErrors::new(errors));
+            }
+        };
+        let errors = validate_base(&base);
+        if errors.is_empty() {
+            Ok(base)
+        } else {
+            Err(crate::ValidationErrors::new(errors))
+        }
+    }
+
+    /// Autoref specialization, so that nested structures are validated iff they implement
+    /// `Validate`.
+    pub struct Validated<'a, T: ?Sized>(pub &'a T);
+
+    pub trait ValidateNested {
+        fn validate_nested(&self, prefix: &str, errors: &mut Vec<crate::ValidationError>);
+    }
+
+    impl<T: crate::Validate + ?Sized> ValidateNested for &Validated<'_, T> {
+        fn validate_nested(&self, prefix: &str, errors: &mut Vec<crate::ValidationError>) {
+            self.0.validate_with_prefix(prefix, errors);
+        }
+    }
+
+    pub trait ValidateNestedFallback {
+        fn validate_nested(&self, prefix: &str, errors: &mut Vec<crate::ValidationError>);
+    }
+
+    impl<T: ?Sized> ValidateNestedFallback for Validated<'_, T> {
+        fn validate_nested(&self, _prefix: &str, _errors: &mut Vec<crate::ValidationError>) {}
+    }
+
+    pub fn push_missing_field(missing: &mut Vec<String>, name: &str) {
+        missing.push(String::from(name));
+    }
+
+    pub fn push_nested_missing_fields<T: crate::Applicable>(
+        missing: &mut Vec<String>,
+        name: &str,
+        nested: &T,
+    ) {
+        missing.extend(
+            nested
+                .missing_fields()
+                .into_iter()
+                .map(|field| alloc::format!("{name}.{field}")),
+        );
+    }
+}
+
+/// Maps a structure to the one generated from it by the optional_struct macro. This is
+/// implemented for every generated structure (unless it is less visible than the original one),
+/// and is what `optional_nested` uses to find the type of a nested optional_struct.
+/// You should never have to implement this manually.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` has no optional_struct that can be found through `HasOptional`",
+    label = "no optional_struct found for `{Self}`",
+    note = "`HasOptional` is only implemented by `#[optional_struct]` when the generated struct is at least as visible as `{Self}`",
+    note = "change the visibility of the generated struct with `vis = ...`, or use `optional_rename` with its name instead"
+)]
+pub trait HasOptional {
+    /// The structure generated by the optional_struct macro.
+    type Optional: Applicable;
+}
+
+/// Allows using `optional_nested` on fields of type `Option<T>`, the same way `optional_rename`
+/// would be used with the structure generated for `T`.
+impl<T: HasOptional> HasOptional for Option<T> {
+    type Optional = T::Optional;
 }
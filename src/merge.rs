@@ -0,0 +1,38 @@
+//! The merge strategies usable with `#[optional_merge(...)]`. Any function with the same signature,
+//! i.e. `fn(&mut T, T)`, can be given instead with `#[optional_merge(with = path::to::function)]`.
+
+use alloc::vec::Vec;
+use core::ops::AddAssign;
+
+/// Appends the new elements after the existing ones.
+pub fn append<T>(existing: &mut Vec<T>, mut new: Vec<T>) {
+    existing.append(&mut new);
+}
+
+/// Extends the existing collection (e.g. a `HashMap`, in which case the new entries win).
+pub fn extend<C, I>(existing: &mut C, new: I)
+where
+    C: Extend<I::Item>,
+    I: IntoIterator,
+{
+    existing.extend(new);
+}
+
+/// Keeps the greatest value.
+pub fn max<T: Ord>(existing: &mut T, new: T) {
+    if new > *existing {
+        *existing = new;
+    }
+}
+
+/// Keeps the smallest value.
+pub fn min<T: Ord>(existing: &mut T, new: T) {
+    if new < *existing {
+        *existing = new;
+    }
+}
+
+/// Adds the new value to the existing one.
+pub fn sum<T: AddAssign>(existing: &mut T, new: T) {
+    *existing += new;
+}
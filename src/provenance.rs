@@ -0,0 +1,114 @@
+use alloc::borrow::ToOwned;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use core::fmt;
+
+use crate::{Applicable, PatchField};
+
+/// An optional_struct merged from several layers (e.g. a file, the environment and the CLI), which
+/// remembers the layer that set every field. Layers are merged the same way `Applicable::apply`
+/// does, i.e. the last layer setting a field wins.
+pub struct TrackedPatch<P> {
+    patch: P,
+    sources: BTreeMap<String, String>,
+}
+
+impl<P: Applicable + Default> Default for TrackedPatch<P> {
+    fn default() -> Self {
+        TrackedPatch {
+            patch: P::default(),
+            sources: BTreeMap::new(),
+        }
+    }
+}
+
+impl<P: Applicable> TrackedPatch<P> {
+    /// Starts from a first layer, labelled with `source` (e.g. a file path, or `"env"`).
+    pub fn new(source: &str, layer: impl Into<P>) -> Self {
+        let patch = layer.into();
+        let mut sources = BTreeMap::new();
+        record_sources(&patch, source, &mut sources);
+        TrackedPatch { patch, sources }
+    }
+
+    /// Applies another layer on top of the previous ones, see `Applicable::apply`.
+    pub fn apply(mut self, source: &str, layer: impl Into<P>) -> Self {
+        let layer = layer.into();
+        record_sources(&layer, source, &mut self.sources);
+        self.patch = self.patch.apply(layer);
+
+        // e.g. the fields of another variant of an enum
+        let mut set_paths = BTreeSet::new();
+        self.patch.visit(&mut |field: &PatchField<'_>| {
+            if field.is_set {
+                set_paths.insert(field.path.to_owned());
+            }
+        });
+        self.sources.retain(|path, _| set_paths.contains(path));
+        self
+    }
+
+    /// The source of the layer that set the field at this (dotted) path, if any.
+    pub fn source_of(&self, path: &str) -> Option<&str> {
+        self.sources.get(path).map(String::as_str)
+    }
+
+    /// The fields that are set, and the source of the layer that set them, ordered by path.
+    pub fn sources(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.sources
+            .iter()
+            .map(|(path, source)| (path.as_str(), source.as_str()))
+    }
+
+    /// The merged optional_struct.
+    pub fn patch(&self) -> &P {
+        &self.patch
+    }
+
+    /// Drops the sources, returning the merged optional_struct.
+    pub fn into_patch(self) -> P {
+        self.patch
+    }
+
+    /// A printable report of the merged fields, annotated with the layer that set them. E.g.
+    /// ```text
+    /// timeout = Some(30) # env
+    /// log_config.log_file = Some("foo.log") # /etc/foo.toml
+    /// log_config.verbose is not set
+    /// ```
+    pub fn report(&self) -> ProvenanceReport<'_, P> {
+        ProvenanceReport(self)
+    }
+}
+
+fn record_sources<P: Applicable>(layer: &P, source: &str, sources: &mut BTreeMap<String, String>) {
+    layer.visit(&mut |field: &PatchField<'_>| {
+        if field.is_set {
+            sources.insert(field.path.to_owned(), source.to_owned());
+        }
+    });
+}
+
+/// See `TrackedPatch::report`.
+pub struct ProvenanceReport<'a, P>(&'a TrackedPatch<P>);
+
+impl<P: Applicable> fmt::Display for ProvenanceReport<'_, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut result = Ok(());
+        self.0.patch.visit(&mut |field: &PatchField<'_>| {
+            if result.is_err() {
+                return;
+            }
+            result = match (field.is_set, field.debug, self.0.source_of(field.path)) {
+                (false, _, _) => writeln!(f, "{} is not set", field.path),
+                (true, Some(value), Some(source)) => {
+                    writeln!(f, "{} = {:?} # {}", field.path, value, source)
+                }
+                (true, None, Some(source)) => writeln!(f, "{} is set # {}", field.path, source),
+                (true, Some(value), None) => writeln!(f, "{} = {:?}", field.path, value),
+                (true, None, None) => writeln!(f, "{} is set", field.path),
+            };
+        });
+        result
+    }
+}
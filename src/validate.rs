@@ -0,0 +1,214 @@
+//! The validations usable with `#[optional_validate(...)]`. Any function with the same signature,
+//! i.e. `fn(&T) -> Result<(), impl Display>`, can be given instead with
+//! `#[optional_validate(with = path::to::function)]`.
+
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::RangeBounds;
+
+/// Implemented by the structures `optional_struct` is used on, checking the fields annotated with
+/// `#[optional_validate(...)]` (recursively, for nested structures). Structures without such fields
+/// (nor nested ones) can implement it by hand instead, e.g. for checks across fields, which the
+/// generated `try_build` runs as well.
+pub trait Validate {
+    /// All the failed validations, empty if the value is valid.
+    fn validate(&self) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+        self.validate_with_prefix("", &mut errors);
+        errors
+    }
+
+    /// Similar to `Validate::validate`, but the paths of the errors start with `prefix` (e.g.
+    /// `"log_config."`).
+    fn validate_with_prefix(&self, prefix: &str, errors: &mut Vec<ValidationError>);
+}
+
+impl<T: Validate> Validate for Option<T> {
+    fn validate_with_prefix(&self, prefix: &str, errors: &mut Vec<ValidationError>) {
+        if let Some(value) = self {
+            value.validate_with_prefix(prefix, errors);
+        }
+    }
+}
+
+/// A field that is either not set, or not valid, and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    path: String,
+    message: String,
+}
+
+impl ValidationError {
+    /// An error for the field at `path`, e.g. `log_config.log_file`.
+    pub fn new(path: String, message: String) -> Self {
+        ValidationError { path, message }
+    }
+
+    /// The dotted path of the field, e.g. `log_config.log_file`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Why the field is not valid, e.g. `must not be empty`.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` {}", self.path, self.message)
+    }
+}
+
+/// The error returned by the generated `try_build`, listing all the fields that are not set or
+/// not valid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationErrors(Vec<ValidationError>);
+
+impl ValidationErrors {
+    /// Wraps the errors, which should not be empty.
+    pub fn new(errors: Vec<ValidationError>) -> Self {
+        ValidationErrors(errors)
+    }
+
+    /// The missing fields first, then the fields that are not valid, in the order of the struct.
+    pub fn errors(&self) -> &[ValidationError] {
+        &self.0
+    }
+
+    /// Similar to `ValidationErrors::errors`, without the borrow.
+    pub fn into_errors(self) -> Vec<ValidationError> {
+        self.0
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, error) in self.0.iter().enumerate() {
+            if i != 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{error}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ValidationErrors {}
+
+/// Checks that the value is in the range, e.g. `#[optional_validate(range(1..=65535))]`.
+pub fn range<T, R>(value: &T, range: R) -> Result<(), String>
+where
+    T: PartialOrd + fmt::Debug,
+    R: RangeBounds<T> + fmt::Debug,
+{
+    if range.contains(value) {
+        Ok(())
+    } else {
+        Err(alloc::format!("must be in {range:?}, got {value:?}"))
+    }
+}
+
+/// Checks that the string or collection is not empty, e.g. `#[optional_validate(non_empty)]`.
+pub fn non_empty<T: HasLength + ?Sized>(value: &T) -> Result<(), String> {
+    if value.is_empty() {
+        Err(String::from("must not be empty"))
+    } else {
+        Ok(())
+    }
+}
+
+/// The types `non_empty` can check (with the `std` feature, also paths, OS strings and hash
+/// collections).
+pub trait HasLength {
+    /// Whether the value has no elements (or characters, for strings).
+    fn is_empty(&self) -> bool;
+}
+
+impl HasLength for str {
+    fn is_empty(&self) -> bool {
+        str::is_empty(self)
+    }
+}
+
+impl HasLength for String {
+    fn is_empty(&self) -> bool {
+        String::is_empty(self)
+    }
+}
+
+impl<T> HasLength for [T] {
+    fn is_empty(&self) -> bool {
+        <[T]>::is_empty(self)
+    }
+}
+
+impl<T> HasLength for Vec<T> {
+    fn is_empty(&self) -> bool {
+        Vec::is_empty(self)
+    }
+}
+
+impl<T> HasLength for VecDeque<T> {
+    fn is_empty(&self) -> bool {
+        VecDeque::is_empty(self)
+    }
+}
+
+impl<K, V> HasLength for BTreeMap<K, V> {
+    fn is_empty(&self) -> bool {
+        BTreeMap::is_empty(self)
+    }
+}
+
+impl<T> HasLength for BTreeSet<T> {
+    fn is_empty(&self) -> bool {
+        BTreeSet::is_empty(self)
+    }
+}
+
+#[cfg(feature = "std")]
+impl HasLength for std::path::Path {
+    fn is_empty(&self) -> bool {
+        self.as_os_str().is_empty()
+    }
+}
+
+#[cfg(feature = "std")]
+impl HasLength for std::path::PathBuf {
+    fn is_empty(&self) -> bool {
+        self.as_os_str().is_empty()
+    }
+}
+
+#[cfg(feature = "std")]
+impl HasLength for std::ffi::OsStr {
+    fn is_empty(&self) -> bool {
+        std::ffi::OsStr::is_empty(self)
+    }
+}
+
+#[cfg(feature = "std")]
+impl HasLength for std::ffi::OsString {
+    fn is_empty(&self) -> bool {
+        self.as_os_str().is_empty()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S> HasLength for std::collections::HashMap<K, V, S> {
+    fn is_empty(&self) -> bool {
+        std::collections::HashMap::is_empty(self)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T, S> HasLength for std::collections::HashSet<T, S> {
+    fn is_empty(&self) -> bool {
+        std::collections::HashSet::is_empty(self)
+    }
+}
@@ -0,0 +1,47 @@
+use optional_struct::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[optional_struct]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[optional_skip_attr(serde)]
+#[optional_attr(serde(rename_all = "UPPERCASE"))]
+struct Config {
+    #[serde(rename = "base_timeout")]
+    #[optional_skip_attr(serde)]
+    #[optional_attr(serde(rename = "patch_timeout"), serde(default))]
+    timeout: u32,
+    #[serde(rename = "log")]
+    log_file: String,
+    level: u8,
+}
+
+#[test]
+fn test_attribute_forwarding() {
+    let config = Config {
+        timeout: 1,
+        log_file: "foo.log".to_owned(),
+        level: 2,
+    };
+    assert_eq!(
+        serde_json::to_value(config).unwrap(),
+        json!({ "base_timeout": 1, "log": "foo.log", "level": 2 })
+    );
+
+    let opt: OptionalConfig =
+        serde_json::from_value(json!({ "log": "bar.log", "LEVEL": 3, "unknown": 0 })).unwrap();
+    assert_eq!(opt.timeout, None);
+    assert_eq!(opt.log_file, Some("bar.log".to_owned()));
+    assert_eq!(opt.level, Some(3));
+
+    assert_eq!(
+        serde_json::to_value(OptionalConfig {
+            timeout: Some(2),
+            log_file: None,
+            level: None,
+        })
+        .unwrap(),
+        json!({ "patch_timeout": 2, "log": null, "LEVEL": null })
+    );
+}
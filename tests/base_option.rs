@@ -1,4 +1,4 @@
-/// MRE from https://github.com/lesurp/OptionalStruct/issues/23
+//! MRE from https://github.com/lesurp/OptionalStruct/issues/23
 
 use optional_struct::*;
 
@@ -0,0 +1,195 @@
+#![cfg(feature = "clap")]
+
+use clap::{CommandFactory, Parser};
+use optional_struct::*;
+
+#[optional_struct(clap = true)]
+#[derive(Debug, PartialEq, Clone)]
+struct LogConfig {
+    /// Where the logs are written
+    log_file: String,
+    verbose: bool,
+}
+
+#[optional_struct(clap = true)]
+#[derive(Debug, PartialEq, Clone)]
+struct Config {
+    /// The port to listen on
+    #[optional_clap(short = 'p', alias = "listen-port")]
+    port: u16,
+    timeout: Option<u32>,
+    #[optional_wrap]
+    comment: Option<String>,
+    #[optional_clap(skip)]
+    plugins: Vec<String>,
+    #[optional_skip_wrap]
+    retries: u8,
+    #[optional_nested]
+    log_config: LogConfig,
+}
+
+#[derive(Parser)]
+struct Cli {
+    #[command(flatten)]
+    config: OptionalConfig,
+}
+
+fn file_config() -> Config {
+    Config {
+        port: 80,
+        timeout: Some(10),
+        comment: None,
+        plugins: vec!["git".to_owned()],
+        retries: 3,
+        log_config: LogConfig {
+            log_file: "foo.log".to_owned(),
+            verbose: false,
+        },
+    }
+}
+
+#[test]
+fn test_clap() {
+    let cli = Cli::try_parse_from([
+        "app",
+        "-p",
+        "8080",
+        "--comment",
+        "hello",
+        "--log-config-verbose",
+    ])
+    .unwrap();
+    assert_eq!(
+        cli.config,
+        OptionalConfig {
+            port: Some(8080),
+            timeout: None,
+            comment: Some(Some("hello".to_owned())),
+            plugins: None,
+            retries: 0,
+            log_config: OptionalLogConfig {
+                log_file: None,
+                verbose: Some(true),
+            },
+        }
+    );
+
+    let config = cli.config.build(file_config());
+    assert_eq!(config.port, 8080);
+    assert_eq!(config.timeout, Some(10));
+    assert_eq!(config.comment, Some("hello".to_owned()));
+    assert!(config.log_config.verbose);
+    assert_eq!(config.log_config.log_file, "foo.log");
+
+    let cli = Cli::try_parse_from(["app", "--log-config-verbose", "false"]).unwrap();
+    assert_eq!(cli.config.log_config.verbose, Some(false));
+    assert_eq!(cli.config.port, None);
+
+    let cli = Cli::try_parse_from(["app", "--listen-port", "1234"]).unwrap();
+    assert_eq!(cli.config.port, Some(1234));
+}
+
+#[test]
+fn test_clap_errors_and_help() {
+    assert!(Cli::try_parse_from(["app", "--port", "not_a_port"]).is_err());
+    assert!(Cli::try_parse_from(["app", "--plugins", "git"]).is_err());
+    assert!(Cli::try_parse_from(["app", "--retries", "1"]).is_err());
+
+    let help = Cli::command().render_help().to_string();
+    assert!(help.contains("The port to listen on"));
+    assert!(help.contains("--log-config-log-file <log-config-log-file>"));
+    assert!(help.contains("Where the logs are written"));
+}
+
+#[optional_struct(clap = true)]
+#[derive(Debug, PartialEq, Clone)]
+struct Inner {
+    a: u8,
+    #[optional_skip_wrap]
+    b: u8,
+}
+
+#[optional_struct(clap = true)]
+#[derive(Debug, PartialEq, Clone)]
+struct Outer {
+    #[optional_wrap]
+    #[optional_nested]
+    inner: Inner,
+    // Its argument `--inner-x` starts like the ones of `inner`
+    inner_x: u8,
+}
+
+#[derive(Parser)]
+struct OuterCli {
+    #[command(flatten)]
+    outer: OptionalOuter,
+}
+
+#[test]
+fn test_clap_wrapped_nested() {
+    // No flag is given, so the nested structure is not set (even though `b` is always set)
+    let cli = OuterCli::try_parse_from(["app"]).unwrap();
+    assert_eq!(cli.outer.inner, None);
+    let base = Outer {
+        inner: Inner { a: 1, b: 5 },
+        inner_x: 0,
+    };
+    assert_eq!(cli.outer.build(base.clone()), base);
+
+    let cli = OuterCli::try_parse_from(["app", "--inner-a", "2"]).unwrap();
+    assert_eq!(cli.outer.inner, Some(OptionalInner { a: Some(2), b: 0 }));
+
+    // Only the arguments of the nested structure count
+    let cli = OuterCli::try_parse_from(["app", "--inner-x", "3"]).unwrap();
+    assert_eq!(cli.outer.inner, None);
+    assert_eq!(cli.outer.inner_x, Some(3));
+}
+
+#[optional_struct(clap = true)]
+#[derive(Debug, PartialEq, Clone)]
+struct Flags {
+    dry_run: core::primitive::bool,
+}
+
+#[derive(Parser)]
+struct FlagsCli {
+    #[command(flatten)]
+    flags: OptionalFlags,
+}
+
+#[test]
+fn test_clap_qualified_bool() {
+    let cli = FlagsCli::try_parse_from(["app", "--dry-run"]).unwrap();
+    assert_eq!(cli.flags.dry_run, Some(true));
+}
+
+#[optional_struct(clap = true)]
+#[derive(Debug, PartialEq, Clone)]
+struct Plugins {
+    names: Vec<String>,
+    #[optional_wrap]
+    ports: Option<Vec<u16>>,
+}
+
+#[derive(Parser)]
+struct PluginsCli {
+    #[command(flatten)]
+    plugins: OptionalPlugins,
+}
+
+#[test]
+fn test_clap_vec() {
+    let cli = PluginsCli::try_parse_from(["app"]).unwrap();
+    assert_eq!(cli.plugins.names, None);
+    assert_eq!(cli.plugins.ports, None);
+
+    // Repeating the argument gives several elements
+    let cli =
+        PluginsCli::try_parse_from(["app", "--names", "git", "--names", "ssh", "--ports", "22"])
+            .unwrap();
+    assert_eq!(
+        cli.plugins.names,
+        Some(vec!["git".to_owned(), "ssh".to_owned()])
+    );
+    assert_eq!(cli.plugins.ports, Some(Some(vec![22])));
+}
@@ -0,0 +1,33 @@
+use optional_struct::*;
+
+// Implements neither Default nor PartialEq
+#[derive(Clone, Debug)]
+struct Handle(u32);
+
+#[optional_struct(derive(Clone, Debug))]
+struct Config {
+    handle: Handle,
+    delay: u32,
+}
+
+#[test]
+fn test_custom_derive() {
+    let mut config = Config {
+        handle: Handle(1),
+        delay: 2,
+    };
+
+    let opt_config = OptionalConfig {
+        handle: Some(Handle(3)),
+        delay: None,
+    };
+
+    opt_config.clone().apply_to(&mut config);
+
+    assert_eq!(config.handle.0, 3);
+    assert_eq!(config.delay, 2);
+    assert_eq!(
+        format!("{opt_config:?}"),
+        "OptionalConfig { handle: Some(Handle(3)), delay: None }"
+    );
+}
@@ -0,0 +1,101 @@
+use optional_struct::*;
+
+#[optional_struct]
+#[derive(Debug, PartialEq)]
+struct Foo {
+    #[optional_default(8080)]
+    port: u16,
+    host: String,
+    #[optional_default(Some("debug".to_owned()))]
+    log_level: Option<String>,
+    #[optional_skip_wrap]
+    name: &'static str,
+    #[optional_nested]
+    #[optional_default(Bar { a: 1, b: 2 })]
+    bar: Bar,
+}
+
+#[optional_struct]
+#[derive(Debug, PartialEq)]
+struct Bar {
+    a: u8,
+    b: u8,
+}
+
+#[optional_struct(default = Baz::base)]
+#[derive(Debug, PartialEq)]
+struct Baz {
+    a: u8,
+    #[optional_default(3)]
+    b: u8,
+    c: u8,
+}
+
+impl Baz {
+    fn base() -> Self {
+        Baz { a: 1, b: 2, c: 4 }
+    }
+}
+
+#[test]
+fn test_build_with_defaults() {
+    let foo = OptionalFoo {
+        port: None,
+        host: None,
+        log_level: None,
+        name: "foo",
+        bar: OptionalBar {
+            a: None,
+            b: Some(3),
+        },
+    }
+    .build_with_defaults();
+
+    assert_eq!(
+        foo,
+        Foo {
+            port: 8080,
+            host: String::new(),
+            log_level: Some("debug".to_owned()),
+            name: "foo",
+            bar: Bar { a: 1, b: 3 },
+        }
+    );
+}
+
+#[test]
+fn test_set_fields_win() {
+    let foo = OptionalFoo {
+        port: Some(1),
+        host: Some("localhost".to_owned()),
+        log_level: Some("info".to_owned()),
+        name: "foo",
+        bar: OptionalBar {
+            a: Some(5),
+            b: Some(6),
+        },
+    }
+    .build_with_defaults();
+
+    assert_eq!(
+        foo,
+        Foo {
+            port: 1,
+            host: "localhost".to_owned(),
+            log_level: Some("info".to_owned()),
+            name: "foo",
+            bar: Bar { a: 5, b: 6 },
+        }
+    );
+}
+
+#[test]
+fn test_default_base() {
+    let baz = OptionalBaz {
+        a: None,
+        b: None,
+        c: Some(5),
+    }
+    .build_with_defaults();
+    assert_eq!(baz, Baz { a: 1, b: 3, c: 5 });
+}
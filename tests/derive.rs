@@ -0,0 +1,72 @@
+use optional_struct::*;
+
+#[derive(OptionalStruct, Debug, PartialEq)]
+struct Foo {
+    bar: u8,
+    #[optional_skip_wrap]
+    baz: bool,
+    #[optional_nested]
+    inner: Inner,
+}
+
+#[derive(Debug, PartialEq, OptionalStruct)]
+#[optional_params(name = InnerPatch, default_wrap = false)]
+struct Inner {
+    #[optional_wrap]
+    a: u8,
+    b: Option<u8>,
+}
+
+#[derive(OptionalStruct, Debug, PartialEq)]
+enum Shape {
+    Circle { radius: u8 },
+    Point,
+}
+
+#[test]
+fn test_derive_struct() {
+    let mut foo = Foo {
+        bar: 1,
+        baz: false,
+        inner: Inner { a: 1, b: None },
+    };
+
+    let opt_foo = OptionalFoo {
+        bar: Some(2),
+        baz: true,
+        inner: InnerPatch {
+            a: None,
+            b: Some(3),
+        },
+    };
+    assert!(!opt_foo.can_convert());
+    opt_foo.apply_to(&mut foo);
+
+    assert_eq!(
+        foo,
+        Foo {
+            bar: 2,
+            baz: true,
+            inner: Inner { a: 1, b: Some(3) },
+        }
+    );
+}
+
+#[test]
+fn test_derive_keeps_other_derives() {
+    let patch = InnerPatch {
+        a: Some(1),
+        b: None,
+    };
+    assert_eq!(patch.clone(), patch);
+    assert_eq!(Inner::try_from(patch), Ok(Inner { a: 1, b: None }));
+}
+
+#[test]
+fn test_derive_enum() {
+    let mut shape = Shape::Point;
+    OptionalShape::Circle { radius: Some(3) }.apply_to(&mut shape);
+    assert_eq!(shape, Shape::Circle { radius: 3 });
+    OptionalShape::Point.apply_to(&mut shape);
+    assert_eq!(shape, Shape::Point);
+}
@@ -0,0 +1,106 @@
+use optional_struct::*;
+
+#[optional_struct]
+#[derive(Debug, PartialEq, Clone)]
+struct LogConfig {
+    log_file: String,
+    verbose: bool,
+}
+
+#[optional_struct]
+#[derive(Debug, PartialEq, Clone)]
+struct Config<T> {
+    name: String,
+    #[optional_rename(OptionalLogConfig)]
+    log_config: LogConfig,
+    #[optional_nested]
+    #[optional_wrap]
+    backup_log_config: LogConfig,
+    comment: Option<String>,
+    #[optional_skip_wrap]
+    value: T,
+}
+
+fn config() -> Config<u8> {
+    Config {
+        name: "foo".to_owned(),
+        log_config: LogConfig {
+            log_file: "foo.log".to_owned(),
+            verbose: false,
+        },
+        backup_log_config: LogConfig {
+            log_file: "backup.log".to_owned(),
+            verbose: false,
+        },
+        comment: None,
+        value: 1,
+    }
+}
+
+#[test]
+fn test_diff() {
+    let old = config();
+    let mut new = config();
+    new.log_config.verbose = true;
+    new.comment = Some("comment".to_owned());
+    new.value = 2;
+
+    let patch = OptionalConfig::diff(&old, &new);
+    assert_eq!(
+        patch,
+        OptionalConfig {
+            name: None,
+            log_config: OptionalLogConfig {
+                log_file: None,
+                verbose: Some(true),
+            },
+            backup_log_config: None,
+            comment: Some("comment".to_owned()),
+            value: 2,
+        }
+    );
+    assert_eq!(patch.build(old), new);
+}
+
+#[test]
+fn test_diff_of_equal_values() {
+    let patch = OptionalLogConfig::diff(&config().log_config, &config().log_config);
+    assert!(patch.is_empty());
+    assert_eq!(patch, OptionalLogConfig::default());
+}
+
+#[optional_struct]
+#[derive(Debug, PartialEq, Clone)]
+struct Inner {
+    a: u8,
+}
+
+#[optional_struct]
+#[derive(Debug, PartialEq, Clone)]
+struct Outer {
+    #[optional_rename(OptionalInner)]
+    inner: Option<Inner>,
+}
+
+#[test]
+fn test_diff_nested_option() {
+    // None -> Some: the whole nested structure is set
+    let old = Outer { inner: None };
+    let new = Outer {
+        inner: Some(Inner { a: 3 }),
+    };
+    let patch = OptionalOuter::diff(&old, &new);
+    assert_eq!(patch.inner, OptionalInner { a: Some(3) });
+    assert_eq!(patch.build(old.clone()), new);
+
+    // Some -> Some
+    let newer = Outer {
+        inner: Some(Inner { a: 4 }),
+    };
+    assert_eq!(OptionalOuter::diff(&new, &newer).build(new.clone()), newer);
+
+    // Some -> None: a patch cannot reset the field, so the change is left out
+    let patch = OptionalOuter::diff(&new, &old);
+    assert!(patch.is_empty());
+    assert_eq!(patch.build(new.clone()), new);
+}
@@ -0,0 +1,109 @@
+use optional_struct::*;
+
+#[optional_struct]
+#[derive(PartialEq, Debug)]
+enum Backend {
+    S3 {
+        bucket: String,
+        region: Option<String>,
+    },
+    Local {
+        path: String,
+    },
+    Tuple(u8, #[optional_rename(OptionalRetries)] Retries),
+    Disabled,
+}
+
+#[optional_struct]
+#[derive(PartialEq, Debug)]
+struct Retries {
+    count: u8,
+}
+
+#[test]
+fn test_apply_same_variant() {
+    let mut backend = Backend::S3 {
+        bucket: "bucket".to_owned(),
+        region: Some("eu".to_owned()),
+    };
+
+    OptionalBackend::S3 {
+        bucket: Some("other".to_owned()),
+        region: None,
+    }
+    .apply_to(&mut backend);
+
+    assert_eq!(
+        backend,
+        Backend::S3 {
+            bucket: "other".to_owned(),
+            region: Some("eu".to_owned()),
+        }
+    );
+}
+
+#[test]
+fn test_apply_other_variant() {
+    let base = Backend::Local {
+        path: "/tmp".to_owned(),
+    };
+
+    let incomplete = OptionalBackend::S3 {
+        bucket: None,
+        region: None,
+    };
+    assert!(!incomplete.can_convert());
+    assert_eq!(
+        incomplete.build(base),
+        Backend::Local {
+            path: "/tmp".to_owned()
+        }
+    );
+
+    let complete = OptionalBackend::S3 {
+        bucket: Some("bucket".to_owned()),
+        region: None,
+    };
+    assert!(complete.can_convert());
+    assert_eq!(
+        complete.build(Backend::Disabled),
+        Backend::S3 {
+            bucket: "bucket".to_owned(),
+            region: None,
+        }
+    );
+}
+
+#[test]
+fn test_apply_opt() {
+    let first = OptionalBackend::Tuple(Some(1), OptionalRetries { count: None });
+    let second = OptionalBackend::Tuple(None, OptionalRetries { count: Some(3) });
+    assert_eq!(
+        first.apply(second),
+        OptionalBackend::Tuple(Some(1), OptionalRetries { count: Some(3) })
+    );
+
+    let replaced = OptionalBackend::Disabled.apply(OptionalBackend::Local { path: None });
+    assert_eq!(replaced, OptionalBackend::Local { path: None });
+}
+
+#[test]
+fn test_try_from() {
+    assert_eq!(
+        Backend::try_from(OptionalBackend::Tuple(
+            Some(1),
+            OptionalRetries { count: Some(2) }
+        )),
+        Ok(Backend::Tuple(1, Retries { count: 2 }))
+    );
+    assert_eq!(
+        Backend::try_from(OptionalBackend::Disabled),
+        Ok(Backend::Disabled)
+    );
+    assert_eq!(
+        Backend::try_from(OptionalBackend::Local { path: None })
+            .unwrap_err()
+            .fields(),
+        ["path"]
+    );
+}
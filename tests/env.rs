@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+
+use optional_struct::*;
+
+#[optional_struct(env = true)]
+#[derive(Debug, PartialEq, Clone)]
+struct LogConfig {
+    log_file: String,
+    verbose: bool,
+}
+
+#[optional_struct(env = true)]
+#[derive(Debug, PartialEq, Clone)]
+struct Config {
+    port: u16,
+    timeout: Option<u32>,
+    #[optional_wrap]
+    comment: Option<String>,
+    #[optional_env("DATABASE_URL")]
+    database: String,
+    #[optional_env(skip)]
+    plugins: Vec<String>,
+    #[optional_nested]
+    log_config: LogConfig,
+}
+
+#[optional_struct(env = true, env_separator = "__")]
+#[derive(Debug, PartialEq, Clone)]
+struct Server {
+    host: String,
+    #[optional_rename(OptionalLogConfig)]
+    logs: LogConfig,
+}
+
+fn vars(vars: &[(&str, &str)]) -> BTreeMap<String, String> {
+    vars.iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_from_env() {
+    let env = vars(&[
+        ("APP_PORT", "8080"),
+        ("APP_COMMENT", "hello"),
+        ("APP_PLUGINS", "ignored"),
+        ("DATABASE_URL", "postgres://localhost"),
+        ("APP_LOG_CONFIG_LOG_FILE", "foo.log"),
+        ("OTHER_PORT", "1"),
+    ]);
+    let config = OptionalConfig::from_env_with("APP", &env).unwrap();
+    assert_eq!(
+        config,
+        OptionalConfig {
+            port: Some(8080),
+            timeout: None,
+            comment: Some(Some("hello".to_owned())),
+            database: Some("postgres://localhost".to_owned()),
+            plugins: None,
+            log_config: OptionalLogConfig {
+                log_file: Some("foo.log".to_owned()),
+                verbose: None,
+            },
+        }
+    );
+
+    // Without a prefix, variables are named after the fields only
+    let env = vars(&[("TIMEOUT", "30"), ("LOG_CONFIG_VERBOSE", "true")]);
+    let config = OptionalConfig::from_env_with("", &env).unwrap();
+    assert_eq!(config.timeout, Some(30));
+    assert_eq!(config.log_config.verbose, Some(true));
+}
+
+#[test]
+fn test_from_env_separator() {
+    let env = vars(&[("SRV__HOST", "localhost"), ("SRV__LOGS_VERBOSE", "false")]);
+    let server = OptionalServer::from_env_with("SRV", &env).unwrap();
+    assert_eq!(server.host, Some("localhost".to_owned()));
+    // The nested structure uses its own separator
+    assert_eq!(server.logs.verbose, Some(false));
+}
+
+#[test]
+fn test_from_env_errors() {
+    let env = vars(&[("APP_PORT", "80"), ("APP_LOG_CONFIG_VERBOSE", "yes")]);
+    let err = OptionalConfig::from_env_with("APP", &env).unwrap_err();
+    assert_eq!(err.variable(), "APP_LOG_CONFIG_VERBOSE");
+    assert_eq!(err.value(), "yes");
+    assert_eq!(
+        err.to_string(),
+        "invalid value \"yes\" for the environment variable `APP_LOG_CONFIG_VERBOSE`: provided string was not `true` or `false`"
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_from_process_env() {
+    std::env::set_var("OPTIONAL_STRUCT_TEST_PORT", "1234");
+    let config = OptionalConfig::from_env("OPTIONAL_STRUCT_TEST").unwrap();
+    assert_eq!(config.port, Some(1234));
+}
+
+#[optional_struct(env = true)]
+#[derive(Debug, PartialEq, Clone)]
+struct Inner {
+    a: u8,
+    #[optional_skip_wrap]
+    b: u8,
+}
+
+#[optional_struct(env = true)]
+#[derive(Debug, PartialEq, Clone)]
+struct Outer {
+    #[optional_wrap]
+    #[optional_nested]
+    inner: Inner,
+}
+
+#[test]
+fn test_from_env_wrapped_nested() {
+    // Nothing is read, so the nested structure is not set (even though `b` is always set)
+    let patch = OptionalOuter::from_env_with("APP", &vars(&[])).unwrap();
+    assert_eq!(patch.inner, None);
+    let base = Outer {
+        inner: Inner { a: 1, b: 5 },
+    };
+    assert_eq!(patch.build(base.clone()), base);
+
+    let patch = OptionalOuter::from_env_with("APP", &vars(&[("APP_INNER_B", "7")])).unwrap();
+    assert_eq!(patch.inner, Some(OptionalInner { a: None, b: 7 }));
+}
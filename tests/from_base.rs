@@ -0,0 +1,118 @@
+use optional_struct::*;
+
+#[optional_struct]
+#[derive(Debug, PartialEq, Clone)]
+struct LogConfig {
+    log_file: String,
+    verbose: bool,
+}
+
+#[optional_struct]
+#[derive(Debug, PartialEq, Clone)]
+struct Config<T> {
+    name: String,
+    #[optional_nested]
+    log_config: LogConfig,
+    #[optional_rename(OptionalLogConfig)]
+    #[optional_wrap]
+    backup_log_config: LogConfig,
+    #[optional_nested]
+    extra_log_config: Option<LogConfig>,
+    comment: Option<String>,
+    #[optional_skip_wrap]
+    value: T,
+}
+
+#[optional_struct]
+#[derive(Debug, PartialEq, Clone)]
+enum Backend {
+    Local { path: String },
+    Remote(#[optional_nested] LogConfig),
+}
+
+fn log_config() -> LogConfig {
+    LogConfig {
+        log_file: "foo.log".to_owned(),
+        verbose: true,
+    }
+}
+
+fn config() -> Config<u8> {
+    Config {
+        name: "foo".to_owned(),
+        log_config: log_config(),
+        backup_log_config: log_config(),
+        extra_log_config: Some(log_config()),
+        comment: Some("comment".to_owned()),
+        value: 1,
+    }
+}
+
+#[test]
+fn test_from_base() {
+    let expected = OptionalConfig {
+        name: Some("foo".to_owned()),
+        log_config: OptionalLogConfig {
+            log_file: Some("foo.log".to_owned()),
+            verbose: Some(true),
+        },
+        backup_log_config: Some(OptionalLogConfig {
+            log_file: Some("foo.log".to_owned()),
+            verbose: Some(true),
+        }),
+        extra_log_config: OptionalLogConfig {
+            log_file: Some("foo.log".to_owned()),
+            verbose: Some(true),
+        },
+        comment: Some("comment".to_owned()),
+        value: 1,
+    };
+    assert_eq!(OptionalConfig::from(&config()), expected);
+    assert_eq!(OptionalConfig::from(config()), expected);
+    assert_eq!(Config::try_from(expected), Ok(config()));
+
+    // Nothing to set in the nested struct
+    let config = Config {
+        extra_log_config: None,
+        ..config()
+    };
+    assert_eq!(
+        OptionalConfig::from(config).extra_log_config,
+        OptionalLogConfig::default()
+    );
+}
+
+#[test]
+fn test_from_base_enum() {
+    let backend = Backend::Remote(log_config());
+    assert_eq!(
+        OptionalBackend::from(&backend),
+        OptionalBackend::Remote(OptionalLogConfig::from(log_config()))
+    );
+    assert_eq!(
+        OptionalBackend::from(Backend::Local {
+            path: "/".to_owned()
+        }),
+        OptionalBackend::Local {
+            path: Some("/".to_owned())
+        }
+    );
+}
+
+#[test]
+fn test_apply_base() {
+    let patch = OptionalLogConfig {
+        log_file: Some("bar.log".to_owned()),
+        verbose: None,
+    };
+    assert_eq!(patch.clone().apply(log_config()), log_config().into());
+    let base = log_config();
+    assert_eq!(
+        OptionalLogConfig::default().apply(&base).apply(patch),
+        OptionalLogConfig {
+            log_file: Some("bar.log".to_owned()),
+            verbose: Some(true),
+        }
+    );
+    assert_eq!(base, log_config());
+}
@@ -0,0 +1,121 @@
+use optional_struct::*;
+
+#[optional_struct]
+#[derive(Debug, PartialEq, Clone)]
+struct LogConfig {
+    log_file: String,
+    verbose: bool,
+}
+
+#[optional_struct]
+#[optional_struct(name = CliConfig)]
+#[derive(Debug, PartialEq, Clone)]
+struct Config {
+    timeout: u32,
+    #[optional_exclude(CliConfig)]
+    threads: u8,
+    #[optional_nested]
+    log_config: LogConfig,
+}
+
+fn defaults() -> Config {
+    Config {
+        timeout: 10,
+        threads: 4,
+        log_config: LogConfig {
+            log_file: "default.log".to_owned(),
+            verbose: false,
+        },
+    }
+}
+
+#[test]
+fn test_layer_stack_priorities() {
+    let mut layers = LayerStack::<Config>::new();
+    assert!(layers.is_empty());
+
+    layers.insert(
+        "cli",
+        20,
+        CliConfig {
+            timeout: Some(30),
+            ..Default::default()
+        },
+    );
+    layers.insert(
+        "file",
+        10,
+        OptionalConfig {
+            timeout: Some(20),
+            threads: None,
+            log_config: OptionalLogConfig {
+                log_file: Some("foo.log".to_owned()),
+                verbose: None,
+            },
+        },
+    );
+    layers.insert("defaults", 0, defaults());
+
+    assert_eq!(layers.len(), 3);
+    assert_eq!(
+        layers.names().collect::<Vec<_>>(),
+        ["defaults", "file", "cli"]
+    );
+    assert_eq!(layers.priority("file"), Some(10));
+
+    let config = layers.try_resolve().unwrap();
+    assert_eq!(config.timeout, 30);
+    assert_eq!(config.log_config.log_file, "foo.log");
+    assert!(!config.log_config.verbose);
+
+    let tracked = layers.resolve_tracked();
+    assert_eq!(tracked.source_of("timeout"), Some("cli"));
+    assert_eq!(tracked.source_of("log_config.log_file"), Some("file"));
+    assert_eq!(tracked.source_of("log_config.verbose"), Some("defaults"));
+}
+
+#[test]
+fn test_layer_stack_updates() {
+    let mut layers = LayerStack::<Config>::new();
+    layers.insert(
+        "file",
+        10,
+        OptionalConfig {
+            timeout: Some(20),
+            ..Default::default()
+        },
+    );
+    assert!(layers.try_resolve().is_err());
+    assert_eq!(layers.resolve(defaults()).timeout, 20);
+
+    // reloading a single layer keeps its priority
+    let reloaded = OptionalConfig {
+        timeout: Some(25),
+        ..Default::default()
+    };
+    assert!(layers.replace("file", reloaded).is_some());
+    assert!(layers.replace("env", OptionalConfig::default()).is_none());
+    assert_eq!(layers.len(), 1);
+    assert_eq!(layers.resolve_patch().timeout, Some(25));
+
+    // same priority: the last inserted layer wins
+    layers.insert(
+        "other_file",
+        10,
+        CliConfig {
+            timeout: Some(40),
+            ..Default::default()
+        },
+    );
+    assert_eq!(layers.resolve_patch().timeout, Some(40));
+
+    // inserting with an existing name replaces the layer
+    let previous = layers.insert("file", 30, OptionalConfig::default());
+    assert_eq!(previous.unwrap().timeout, Some(25));
+    assert_eq!(layers.names().collect::<Vec<_>>(), ["other_file", "file"]);
+
+    assert!(layers.remove("other_file").is_some());
+    assert!(layers.remove("other_file").is_none());
+    assert_eq!(layers.resolve(defaults()), defaults());
+    assert!(layers.get("file").is_some());
+}
@@ -0,0 +1,70 @@
+use optional_struct::*;
+
+#[optional_struct]
+#[derive(Debug, PartialEq)]
+struct Args<'a, 'b: 'a> {
+    name: &'a str,
+    tags: &'a [&'b str],
+    comment: Option<&'a str>,
+}
+
+fn parse(buffer: &str) -> OptionalArgs<'_, 'static> {
+    let mut args = OptionalArgs::default();
+    for line in buffer.lines() {
+        match line.split_once('=') {
+            Some(("name", name)) => args.name = Some(name),
+            Some(("comment", comment)) => args.comment = Some(comment),
+            _ => {}
+        }
+    }
+    args
+}
+
+#[test]
+fn test_borrowed_fields() {
+    let buffer = "name=from_buffer\ncomment=zero-copy".to_owned();
+    let tags = ["a", "b"];
+
+    let base = Args {
+        name: "base",
+        tags: &tags,
+        comment: None,
+    };
+
+    let from_buffer = parse(&buffer);
+    assert!(!from_buffer.can_convert());
+
+    let args = OptionalArgs {
+        tags: Some(&tags[1..]),
+        ..Default::default()
+    }
+    .apply(from_buffer)
+    .build(base);
+
+    assert_eq!(
+        args,
+        Args {
+            name: "from_buffer",
+            tags: &["b"],
+            comment: Some("zero-copy"),
+        }
+    );
+}
+
+#[test]
+fn test_try_from_borrowed() {
+    let buffer = "name=foo".to_owned();
+    let patch = parse(&buffer).apply(OptionalArgs {
+        tags: Some(&[]),
+        ..Default::default()
+    });
+
+    assert_eq!(
+        Args::try_from(patch),
+        Ok(Args {
+            name: "foo",
+            tags: &[],
+            comment: None,
+        })
+    );
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use optional_struct::*;
+
+fn keep_longest(existing: &mut String, new: String) {
+    if new.len() > existing.len() {
+        *existing = new;
+    }
+}
+
+#[optional_struct]
+#[derive(Debug, PartialEq, Clone)]
+struct Config {
+    #[optional_merge(append)]
+    plugins: Vec<String>,
+    #[optional_merge(extend)]
+    aliases: HashMap<String, String>,
+    #[optional_merge(max)]
+    max_connections: u32,
+    #[optional_merge(min)]
+    timeout: Option<u32>,
+    #[optional_merge(sum)]
+    #[optional_skip_wrap]
+    retries: u8,
+    #[optional_merge(with = keep_longest)]
+    name: String,
+    log_file: String,
+}
+
+fn base() -> Config {
+    Config {
+        plugins: vec!["core".to_owned()],
+        aliases: HashMap::from([("ll".to_owned(), "ls -l".to_owned())]),
+        max_connections: 10,
+        timeout: None,
+        retries: 1,
+        name: "app".to_owned(),
+        log_file: "a.log".to_owned(),
+    }
+}
+
+fn system() -> OptionalConfig {
+    OptionalConfig {
+        plugins: Some(vec!["git".to_owned()]),
+        aliases: Some(HashMap::from([("la".to_owned(), "ls -a".to_owned())])),
+        max_connections: Some(5),
+        timeout: Some(30),
+        retries: 2,
+        name: Some("application".to_owned()),
+        log_file: Some("system.log".to_owned()),
+    }
+}
+
+fn user() -> OptionalConfig {
+    OptionalConfig {
+        plugins: Some(vec!["vim".to_owned()]),
+        aliases: Some(HashMap::from([("ll".to_owned(), "ls -lh".to_owned())])),
+        max_connections: Some(20),
+        timeout: Some(60),
+        retries: 3,
+        name: Some("app2".to_owned()),
+        log_file: Some("user.log".to_owned()),
+    }
+}
+
+#[test]
+fn test_merge_strategies() {
+    let config = user().build(system().build(base()));
+    assert_eq!(config.plugins, ["core", "git", "vim"]);
+    assert_eq!(
+        config.aliases,
+        HashMap::from([
+            ("ll".to_owned(), "ls -lh".to_owned()),
+            ("la".to_owned(), "ls -a".to_owned()),
+        ])
+    );
+    assert_eq!(config.max_connections, 20);
+    assert_eq!(config.timeout, Some(30));
+    assert_eq!(config.retries, 6);
+    assert_eq!(config.name, "application");
+    // Fields without a strategy are still replaced
+    assert_eq!(config.log_file, "user.log");
+}
+
+#[test]
+fn test_merge_strategies_consistency() {
+    // Merging the layers first gives the same result as applying them one after the other
+    let merged = system().apply(user());
+    assert_eq!(
+        merged.plugins,
+        Some(vec!["git".to_owned(), "vim".to_owned()])
+    );
+    assert_eq!(merged.retries, 5);
+    assert_eq!(
+        merged.clone().build(base()),
+        user().build(system().build(base()))
+    );
+
+    // Unset values are left untouched
+    let empty = OptionalConfig::default();
+    assert_eq!(merged.clone().apply(empty.clone()), merged);
+    assert_eq!(empty.build(base()), base());
+}
+
+#[optional_struct]
+#[derive(Debug, PartialEq, Clone)]
+struct Plugins {
+    #[optional_merge(append)]
+    #[optional_default(vec![1])]
+    plugins: Vec<u8>,
+    #[optional_merge(sum)]
+    #[optional_skip_wrap]
+    #[optional_default(10)]
+    retries: u8,
+}
+
+#[test]
+fn test_merge_strategies_with_defaults() {
+    let patch = OptionalPlugins {
+        plugins: Some(vec![2]),
+        retries: 2,
+    };
+    let defaults = Plugins {
+        plugins: vec![1],
+        retries: 10,
+    };
+    // The defaults are merged with the patch, as `build` does with the base
+    assert_eq!(patch.clone().build_with_defaults(), patch.build(defaults));
+    assert_eq!(
+        OptionalPlugins {
+            plugins: Some(vec![2]),
+            retries: 2,
+        }
+        .build_with_defaults(),
+        Plugins {
+            plugins: vec![1, 2],
+            retries: 12,
+        }
+    );
+}
@@ -0,0 +1,81 @@
+use optional_struct::*;
+
+#[optional_struct]
+#[derive(Debug, PartialEq)]
+struct LogConfig {
+    log_file: String,
+    verbose: bool,
+}
+
+#[optional_struct]
+#[derive(Debug, PartialEq)]
+struct Config {
+    name: String,
+    #[optional_nested]
+    log_config: LogConfig,
+    #[optional_nested]
+    #[optional_wrap]
+    backup_log_config: LogConfig,
+    comment: Option<String>,
+}
+
+#[test]
+fn test_missing_fields() {
+    let config = OptionalConfig {
+        name: None,
+        log_config: OptionalLogConfig {
+            log_file: None,
+            verbose: Some(true),
+        },
+        backup_log_config: Some(OptionalLogConfig::default()),
+        comment: None,
+    };
+    assert_eq!(
+        config.missing_fields(),
+        [
+            "name",
+            "log_config.log_file",
+            "backup_log_config.log_file",
+            "backup_log_config.verbose"
+        ]
+    );
+
+    let err = Config::try_from(config.clone()).unwrap_err();
+    assert_eq!(err.fields(), config.missing_fields());
+    assert_eq!(
+        err.to_string(),
+        "missing fields: `name`, `log_config.log_file`, `backup_log_config.log_file`, `backup_log_config.verbose`"
+    );
+    assert_eq!(err.into_patch(), config);
+}
+
+#[test]
+fn test_no_missing_fields() {
+    let config = OptionalConfig {
+        name: Some("foo".to_owned()),
+        log_config: OptionalLogConfig {
+            log_file: Some("foo.log".to_owned()),
+            verbose: Some(true),
+        },
+        backup_log_config: None,
+        comment: None,
+    };
+    assert_eq!(config.missing_fields(), ["backup_log_config"]);
+
+    let config = OptionalConfig {
+        backup_log_config: Some(config.log_config.clone()),
+        ..config
+    };
+    assert!(config.missing_fields().is_empty());
+    assert!(Config::try_from(config).is_ok());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_error_trait() {
+    fn source(e: &dyn std::error::Error) -> String {
+        e.to_string()
+    }
+    let err = LogConfig::try_from(OptionalLogConfig::default()).unwrap_err();
+    assert_eq!(source(&err), "missing fields: `log_file`, `verbose`");
+}
@@ -0,0 +1,27 @@
+use optional_struct::*;
+
+#[optional_struct(name = PartialConfig, default_wrap = false)]
+struct Config {
+    timeout: Option<u32>,
+
+    #[optional_wrap]
+    path: String,
+}
+
+#[test]
+fn test_named_parameters() {
+    let mut config = Config {
+        timeout: Some(2),
+        path: "/var/log/foo.log".to_owned(),
+    };
+
+    let opt_config = PartialConfig {
+        timeout: None,
+        path: Some("/tmp/bar.log".to_owned()),
+    };
+
+    opt_config.apply_to(&mut config);
+
+    assert_eq!(config.timeout, Some(2));
+    assert_eq!(config.path, "/tmp/bar.log");
+}
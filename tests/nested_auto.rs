@@ -0,0 +1,63 @@
+use optional_struct::*;
+
+#[optional_struct]
+struct Config {
+    timeout: Option<u32>,
+
+    #[optional_nested]
+    #[optional_wrap]
+    log_config: LogConfig,
+
+    #[optional_nested]
+    rotation: Option<Rotation>,
+
+    #[optional_nested]
+    counter: Counter<u8>,
+}
+
+#[optional_struct(name = LogPatch)]
+struct LogConfig {
+    log_file: String,
+    log_level: usize,
+}
+
+#[optional_struct(PatchRotation)]
+struct Rotation {
+    keep: usize,
+}
+
+#[optional_struct]
+struct Counter<T> {
+    value: T,
+}
+
+#[test]
+fn test_nested_without_rename() {
+    let mut config = Config {
+        timeout: Some(2),
+        log_config: LogConfig {
+            log_file: "/var/log/foobar.log".to_owned(),
+            log_level: 3,
+        },
+        rotation: None,
+        counter: Counter { value: 1 },
+    };
+
+    let opt_config = OptionalConfig {
+        timeout: None,
+        log_config: Some(LogPatch {
+            log_file: Some("/tmp/bar.log".to_owned()),
+            log_level: None,
+        }),
+        rotation: PatchRotation { keep: Some(4) },
+        counter: OptionalCounter { value: Some(5) },
+    };
+
+    opt_config.apply_to(&mut config);
+
+    assert_eq!(config.timeout, Some(2));
+    assert_eq!(config.log_config.log_file, "/tmp/bar.log");
+    assert_eq!(config.log_config.log_level, 3);
+    assert_eq!(config.rotation.map(|r| r.keep), Some(4));
+    assert_eq!(config.counter.value, 5);
+}
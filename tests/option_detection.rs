@@ -0,0 +1,52 @@
+use optional_struct::*;
+
+mod lookalike {
+    #[derive(Clone, Debug, PartialEq, Default)]
+    pub struct Option<T>(pub T);
+}
+
+type MaybePort = Option<u16>;
+
+#[optional_struct]
+struct Config {
+    full_path: std::option::Option<u32>,
+    lookalike: lookalike::Option<u8>,
+    #[optional_is_option]
+    port: MaybePort,
+    #[optional_not_option]
+    #[optional_skip_wrap]
+    name: String,
+}
+
+#[test]
+fn test_option_detection() {
+    let mut config = Config {
+        full_path: Some(1),
+        lookalike: lookalike::Option(1),
+        port: Some(80),
+        name: "foo".to_owned(),
+    };
+
+    let opt_config = OptionalConfig {
+        full_path: None,
+        lookalike: Some(lookalike::Option(2)),
+        port: None,
+        name: "bar".to_owned(),
+    };
+
+    opt_config.apply_to(&mut config);
+
+    assert_eq!(config.full_path, Some(1));
+    assert_eq!(config.lookalike, lookalike::Option(2));
+    assert_eq!(config.port, Some(80));
+    assert_eq!(config.name, "bar");
+
+    OptionalConfig {
+        full_path: None,
+        lookalike: None,
+        port: Some(8080),
+        name: "baz".to_owned(),
+    }
+    .apply_to(&mut config);
+    assert_eq!(config.port, Some(8080));
+}
@@ -0,0 +1,84 @@
+use optional_struct::*;
+
+#[optional_struct]
+#[derive(Debug, PartialEq, Clone)]
+struct LogConfig {
+    log_file: String,
+    verbose: bool,
+}
+
+#[optional_struct]
+#[derive(Debug, PartialEq, Clone)]
+struct Config {
+    timeout: u32,
+    #[optional_nested]
+    log_config: LogConfig,
+    comment: Option<String>,
+}
+
+#[test]
+fn test_provenance() {
+    let defaults = Config {
+        timeout: 10,
+        log_config: LogConfig {
+            log_file: "default.log".to_owned(),
+            verbose: false,
+        },
+        comment: None,
+    };
+    let file = OptionalConfig {
+        timeout: Some(20),
+        log_config: OptionalLogConfig {
+            log_file: Some("foo.log".to_owned()),
+            verbose: None,
+        },
+        comment: None,
+    };
+    let env = OptionalConfig {
+        timeout: Some(30),
+        ..Default::default()
+    };
+
+    let merged = TrackedPatch::<OptionalConfig>::new("defaults", &defaults)
+        .apply("/etc/foo.toml", file)
+        .apply("env", env);
+
+    assert_eq!(merged.source_of("timeout"), Some("env"));
+    assert_eq!(
+        merged.source_of("log_config.log_file"),
+        Some("/etc/foo.toml")
+    );
+    assert_eq!(merged.source_of("log_config.verbose"), Some("defaults"));
+    assert_eq!(merged.source_of("comment"), None);
+    assert_eq!(
+        merged.sources().collect::<Vec<_>>(),
+        [
+            ("log_config.log_file", "/etc/foo.toml"),
+            ("log_config.verbose", "defaults"),
+            ("timeout", "env"),
+        ]
+    );
+    assert_eq!(
+        merged.report().to_string(),
+        "timeout = Some(30) # env\n\
+         log_config.log_file = Some(\"foo.log\") # /etc/foo.toml\n\
+         log_config.verbose = Some(false) # defaults\n\
+         comment is not set\n"
+    );
+
+    let config = Config::try_from(merged.into_patch()).unwrap();
+    assert_eq!(config.timeout, 30);
+}
+
+#[test]
+fn test_provenance_default() {
+    let merged = TrackedPatch::<OptionalLogConfig>::default().apply(
+        "cli",
+        OptionalLogConfig {
+            log_file: None,
+            verbose: Some(true),
+        },
+    );
+    assert_eq!(merged.source_of("verbose"), Some("cli"));
+    assert_eq!(merged.patch().count_set(), 1);
+}
@@ -0,0 +1,122 @@
+use optional_struct::*;
+
+#[optional_struct]
+#[derive(Debug, PartialEq)]
+struct LogConfig {
+    log_file: String,
+    verbose: bool,
+}
+
+struct NotDebug;
+
+#[optional_struct(derive(Default))]
+struct Config<'a, T> {
+    name: &'a str,
+    #[optional_nested]
+    log_config: LogConfig,
+    #[optional_nested]
+    #[optional_wrap]
+    backup_log_config: LogConfig,
+    comment: Option<String>,
+    #[optional_skip_wrap]
+    value: T,
+    not_debug: NotDebug,
+}
+
+impl Default for NotDebug {
+    fn default() -> Self {
+        NotDebug
+    }
+}
+
+#[test]
+fn test_field_names() {
+    assert_eq!(OptionalLogConfig::FIELD_NAMES, ["log_file", "verbose"]);
+    assert_eq!(
+        OptionalConfig::<u8>::FIELD_NAMES,
+        [
+            "name",
+            "log_config",
+            "backup_log_config",
+            "comment",
+            "value",
+            "not_debug"
+        ]
+    );
+}
+
+#[test]
+fn test_visit() {
+    let config = OptionalConfig {
+        name: Some("foo"),
+        log_config: OptionalLogConfig {
+            log_file: None,
+            verbose: Some(true),
+        },
+        backup_log_config: None,
+        comment: None,
+        value: 3u8,
+        not_debug: None,
+    };
+
+    let mut visited = Vec::new();
+    config.visit(&mut |field: &PatchField<'_>| {
+        visited.push((
+            field.path.to_owned(),
+            field.is_set,
+            field.debug.map(|d| format!("{d:?}")),
+            field.any.is_some(),
+        ));
+    });
+    assert_eq!(
+        visited,
+        [
+            (
+                "name".to_owned(),
+                true,
+                Some("Some(\"foo\")".to_owned()),
+                false
+            ),
+            (
+                "log_config.log_file".to_owned(),
+                false,
+                Some("None".to_owned()),
+                true
+            ),
+            (
+                "log_config.verbose".to_owned(),
+                true,
+                Some("Some(true)".to_owned()),
+                true
+            ),
+            (
+                "backup_log_config".to_owned(),
+                false,
+                Some("None".to_owned()),
+                true
+            ),
+            ("comment".to_owned(), false, Some("None".to_owned()), true),
+            ("value".to_owned(), true, None, false),
+            ("not_debug".to_owned(), false, None, true),
+        ]
+    );
+
+    assert_eq!(config.count_set(), 3);
+    assert!(!config.is_empty());
+}
+
+#[test]
+fn test_any() {
+    let log_config = OptionalLogConfig {
+        log_file: Some("foo.log".to_owned()),
+        verbose: None,
+    };
+    let mut log_file = None;
+    log_config.visit(&mut |field: &PatchField<'_>| {
+        if let Some(v) = field.any.and_then(|a| a.downcast_ref::<Option<String>>()) {
+            log_file = v.clone();
+        }
+    });
+    assert_eq!(log_file.as_deref(), Some("foo.log"));
+    assert!(OptionalLogConfig::default().is_empty());
+}
@@ -0,0 +1,57 @@
+use optional_struct::*;
+
+mod log {
+    use optional_struct::*;
+
+    #[optional_struct]
+    pub struct LogConfig {
+        pub log_file: String,
+    }
+}
+
+#[optional_struct]
+struct Wrapper<T> {
+    value: T,
+}
+
+#[optional_struct]
+struct Config<T> {
+    #[optional_rename(crate::log::OptionalLogConfig)]
+    log_config: log::LogConfig,
+
+    #[optional_rename(OptionalWrapper<T>)]
+    #[optional_wrap]
+    wrapped: Wrapper<T>,
+
+    #[optional_rename(OptionalWrapper<u8>)]
+    maybe_wrapped: Option<Wrapper<u8>>,
+}
+
+#[test]
+fn test_rename_with_path_and_generics() {
+    let mut config = Config {
+        log_config: log::LogConfig {
+            log_file: "/var/log/foo.log".to_owned(),
+        },
+        wrapped: Wrapper { value: 'a' },
+        maybe_wrapped: None,
+    };
+
+    let opt_config = OptionalConfig {
+        log_config: log::OptionalLogConfig {
+            log_file: Some("/tmp/bar.log".to_owned()),
+        },
+        wrapped: Some(OptionalWrapper { value: Some('b') }),
+        maybe_wrapped: OptionalWrapper { value: Some(1) },
+    };
+    assert!(opt_config.can_convert());
+
+    opt_config.clone().apply_to(&mut config);
+
+    assert_eq!(config.log_config.log_file, "/tmp/bar.log");
+    assert_eq!(config.wrapped.value, 'b');
+    assert_eq!(config.maybe_wrapped.map(|w| w.value), Some(1));
+
+    let converted = Config::try_from(opt_config).ok().unwrap();
+    assert_eq!(converted.wrapped.value, 'b');
+}
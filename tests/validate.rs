@@ -0,0 +1,184 @@
+use optional_struct::*;
+
+fn no_spaces(value: &String) -> Result<(), String> {
+    if value.contains(' ') {
+        Err(format!("must not contain spaces, got {value:?}"))
+    } else {
+        Ok(())
+    }
+}
+
+#[optional_struct]
+#[derive(Debug, PartialEq, Clone)]
+struct LogConfig {
+    #[optional_validate(non_empty, with = no_spaces)]
+    log_file: String,
+    verbose: bool,
+}
+
+#[optional_struct]
+#[derive(Debug, PartialEq, Clone)]
+struct Config {
+    #[optional_validate(range(1..=65535))]
+    port: u32,
+    #[optional_validate(range(..100))]
+    retries: Option<u8>,
+    #[optional_validate(non_empty)]
+    plugins: Vec<String>,
+    #[optional_nested]
+    log_config: LogConfig,
+}
+
+fn valid() -> Config {
+    Config {
+        port: 8080,
+        retries: None,
+        plugins: vec!["git".to_owned()],
+        log_config: LogConfig {
+            log_file: "foo.log".to_owned(),
+            verbose: false,
+        },
+    }
+}
+
+#[test]
+fn test_validate() {
+    assert!(valid().validate().is_empty());
+
+    let mut config = valid();
+    config.port = 0;
+    config.retries = Some(200);
+    config.log_config.log_file = "my file.log".to_owned();
+    let errors = config.validate();
+    let paths = errors.iter().map(|e| e.path()).collect::<Vec<_>>();
+    assert_eq!(paths, ["port", "retries", "log_config.log_file"]);
+    assert_eq!(errors[0].message(), "must be in 1..=65535, got 0");
+    assert_eq!(
+        errors[2].to_string(),
+        "`log_config.log_file` must not contain spaces, got \"my file.log\""
+    );
+}
+
+#[test]
+fn test_try_build() {
+    let patch = OptionalConfig::from(valid());
+    assert_eq!(patch.clone().try_build(), Ok(valid()));
+
+    let mut invalid = patch.clone();
+    invalid.plugins = Some(vec![]);
+    invalid.log_config.log_file = Some(String::new());
+    let errors = invalid.try_build().unwrap_err();
+    assert_eq!(
+        errors.to_string(),
+        "`plugins` must not be empty, `log_config.log_file` must not be empty"
+    );
+
+    // Missing fields are reported first, followed by the set fields that are not valid
+    let mut incomplete = patch;
+    incomplete.port = None;
+    incomplete.retries = Some(200);
+    incomplete.log_config.verbose = None;
+    incomplete.log_config.log_file = Some("my file.log".to_owned());
+    let errors = incomplete.try_build().unwrap_err();
+    let paths = errors.errors().iter().map(|e| e.path()).collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        [
+            "port",
+            "log_config.verbose",
+            "retries",
+            "log_config.log_file"
+        ]
+    );
+    assert_eq!(errors.errors()[0].message(), "is not set");
+    assert_eq!(errors.errors()[2].message(), "must be in ..100, got 200");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_non_empty_path() {
+    use std::path::PathBuf;
+
+    #[optional_struct]
+    struct Paths {
+        #[optional_validate(non_empty)]
+        data_dir: PathBuf,
+    }
+
+    let paths = Paths {
+        data_dir: PathBuf::from("/var/lib/app"),
+    };
+    assert!(paths.validate().is_empty());
+
+    let paths = Paths {
+        data_dir: PathBuf::new(),
+    };
+    let errors = paths.validate();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].to_string(), "`data_dir` must not be empty");
+}
+
+#[test]
+fn test_validate_patch() {
+    #[optional_struct]
+    struct Limits {
+        #[optional_wrap]
+        #[optional_validate(range(1..))]
+        max: Option<u32>,
+        #[optional_skip_wrap]
+        #[optional_validate(non_empty)]
+        name: String,
+    }
+
+    // Only the set fields are validated
+    let patch = OptionalLimits {
+        max: None,
+        name: "limits".to_owned(),
+    };
+    assert!(patch.validate().is_empty());
+
+    let patch = OptionalLimits {
+        max: Some(Some(0)),
+        name: String::new(),
+    };
+    let errors = patch.validate();
+    let paths = errors.iter().map(|e| e.path()).collect::<Vec<_>>();
+    assert_eq!(paths, ["max", "name"]);
+}
+
+#[optional_struct]
+#[derive(Debug, PartialEq)]
+struct Bounds {
+    min: u32,
+    max: u32,
+}
+
+// Without `optional_validate`, checks across fields can be written by hand
+impl Validate for Bounds {
+    fn validate_with_prefix(&self, prefix: &str, errors: &mut Vec<ValidationError>) {
+        if self.min > self.max {
+            errors.push(ValidationError::new(
+                format!("{prefix}min"),
+                format!("must not be greater than `max` ({})", self.max),
+            ));
+        }
+    }
+}
+
+#[test]
+fn test_hand_written_validate() {
+    let bounds = OptionalBounds {
+        min: Some(1),
+        max: Some(2),
+    };
+    assert_eq!(bounds.try_build(), Ok(Bounds { min: 1, max: 2 }));
+
+    let bounds = OptionalBounds {
+        min: Some(3),
+        max: Some(2),
+    };
+    assert_eq!(
+        bounds.try_build().unwrap_err().to_string(),
+        "`min` must not be greater than `max` (2)"
+    );
+}
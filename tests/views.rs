@@ -0,0 +1,124 @@
+use optional_struct::*;
+
+#[optional_struct(name = ConfigFileLayer)]
+#[optional_struct(name = CliLayer)]
+#[optional_struct(name = EnvLayer, default_wrap = false)]
+#[derive(Debug, PartialEq)]
+struct Config {
+    #[optional_exclude(CliLayer)]
+    log_file: String,
+    verbose: bool,
+    #[optional_include(ConfigFileLayer)]
+    retries: Option<u8>,
+}
+
+#[derive(OptionalStruct, Debug, PartialEq)]
+#[optional_params(name = FullPatch)]
+#[optional_params(name = NamePatch)]
+struct Person {
+    name: String,
+    #[optional_exclude(NamePatch)]
+    age: u8,
+}
+
+#[test]
+fn test_views() {
+    let mut config = Config {
+        log_file: "a.log".to_owned(),
+        verbose: false,
+        retries: None,
+    };
+
+    let cli = CliLayer {
+        verbose: Some(true),
+    };
+    assert!(!cli.can_convert());
+    assert_eq!(cli.missing_fields(), ["log_file", "retries"]);
+    cli.apply_to(&mut config);
+
+    let file = ConfigFileLayer {
+        log_file: Some("b.log".to_owned()),
+        verbose: None,
+        retries: Some(3),
+    };
+    assert!(!file.can_convert());
+    file.apply_to(&mut config);
+
+    assert_eq!(
+        config,
+        Config {
+            log_file: "b.log".to_owned(),
+            verbose: true,
+            retries: Some(3),
+        }
+    );
+
+    // Not all the fields are in this view, so it can never be converted to the base
+    let env = EnvLayer {
+        log_file: "c.log".to_owned(),
+        verbose: false,
+    };
+    assert!(!env.can_convert());
+    env.apply_to(&mut config);
+    assert_eq!(config.log_file, "c.log");
+}
+
+#[test]
+fn test_view_conversions() {
+    let cli = CliLayer {
+        verbose: Some(true),
+    };
+    let file = ConfigFileLayer::from(cli.clone());
+    assert_eq!(
+        file,
+        ConfigFileLayer {
+            log_file: None,
+            verbose: Some(true),
+            retries: None,
+        }
+    );
+    assert_eq!(CliLayer::from(file), cli);
+
+    let patch = NamePatch::from(FullPatch {
+        name: Some("Bob".to_owned()),
+        age: Some(3),
+    });
+    assert_eq!(
+        patch.build(Person {
+            name: "Alice".to_owned(),
+            age: 2
+        }),
+        Person {
+            name: "Bob".to_owned(),
+            age: 2
+        }
+    );
+}
+
+#[optional_struct(ServerFileLayer, ServerCliLayer, derive(Debug, Clone, PartialEq))]
+#[derive(Debug, PartialEq)]
+struct Server {
+    #[optional_include(ServerFileLayer)]
+    host: String,
+    port: u16,
+}
+
+#[test]
+fn test_listed_views() {
+    let cli = ServerCliLayer { port: Some(8080) };
+    let file = ServerFileLayer::from(cli.clone());
+    assert_eq!(
+        file,
+        ServerFileLayer {
+            host: None,
+            port: Some(8080)
+        }
+    );
+    assert_eq!(ServerCliLayer::from(file), cli);
+
+    let server = cli.build(Server {
+        host: "localhost".to_owned(),
+        port: 80,
+    });
+    assert_eq!(server.port, 8080);
+}
@@ -0,0 +1,45 @@
+mod config {
+    use optional_struct::*;
+
+    #[optional_struct(vis = pub(crate), field_vis = inherit)]
+    pub struct Config {
+        pub delay: u32,
+        secret: String,
+    }
+
+    impl Config {
+        pub fn new() -> Self {
+            Config {
+                delay: 1,
+                secret: "hunter2".to_owned(),
+            }
+        }
+
+        pub fn secret(&self) -> &str {
+            &self.secret
+        }
+    }
+
+    impl OptionalConfig {
+        pub fn with_secret(secret: &str) -> Self {
+            OptionalConfig {
+                secret: Some(secret.to_owned()),
+                ..Default::default()
+            }
+        }
+    }
+}
+
+use config::*;
+use optional_struct::Applicable;
+
+#[test]
+fn test_visibility() {
+    let mut patch = OptionalConfig::with_secret("correct horse");
+    patch.delay = Some(3);
+
+    let config = patch.build(Config::new());
+
+    assert_eq!(config.delay, 3);
+    assert_eq!(config.secret(), "correct horse");
+}
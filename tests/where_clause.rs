@@ -0,0 +1,60 @@
+use std::fmt::Debug;
+
+use optional_struct::*;
+
+trait Backend {
+    type Handle: Clone + Debug + PartialEq + Default;
+}
+
+struct Local;
+
+impl Backend for Local {
+    type Handle = u32;
+}
+
+#[optional_struct]
+struct Config<B>
+where
+    B: Backend,
+{
+    handle: B::Handle,
+    name: String,
+}
+
+#[optional_struct(bound = "T: Copy + Into<u64>")]
+struct Counter<T> {
+    value: T,
+}
+
+fn total<T>(counter: OptionalCounter<T>) -> u64
+where
+    T: Copy + Into<u64>,
+{
+    counter.value.map(Into::into).unwrap_or(0)
+}
+
+#[test]
+fn test_where_clause() {
+    let mut config = Config::<Local> {
+        handle: 1,
+        name: "foo".to_owned(),
+    };
+
+    let opt_config = OptionalConfig::<Local> {
+        handle: Some(2),
+        name: None,
+    };
+    assert!(!opt_config.can_convert());
+
+    opt_config.apply_to(&mut config);
+
+    assert_eq!(config.handle, 2);
+    assert_eq!(config.name, "foo");
+}
+
+#[test]
+fn test_extra_bound() {
+    let counter = OptionalCounter { value: Some(3u8) };
+    assert_eq!(total(counter.clone()), 3);
+    assert_eq!(Counter::try_from(counter).ok().map(|c| c.value), Some(3));
+}
@@ -0,0 +1,57 @@
+#![allow(clippy::non_minimal_cfg)]
+use optional_struct::*;
+
+#[optional_struct]
+#[derive(Debug, PartialEq)]
+struct Foo {
+    #[cfg(test)]
+    #[cfg(all())]
+    bar: u8,
+    #[cfg(test)]
+    #[cfg(any())]
+    baz: u8,
+    #[cfg_attr(test, optional_skip_wrap)]
+    skipped: u8,
+    #[cfg_attr(not(test), optional_skip_wrap)]
+    wrapped: u8,
+    #[cfg_attr(all(), cfg_attr(test, optional_wrap, allow(unused)))]
+    double_wrapped: Option<u8>,
+}
+
+#[test]
+fn test_cfg_and_cfg_attr() {
+    let mut foo = Foo {
+        bar: 1,
+        skipped: 1,
+        wrapped: 1,
+        double_wrapped: Some(1),
+    };
+
+    let opt_foo = OptionalFoo {
+        bar: Some(2),
+        skipped: 2,
+        wrapped: None,
+        double_wrapped: Some(None),
+    };
+    assert!(!opt_foo.can_convert());
+    opt_foo.apply_to(&mut foo);
+
+    assert_eq!(
+        foo,
+        Foo {
+            bar: 2,
+            skipped: 2,
+            wrapped: 1,
+            double_wrapped: None,
+        }
+    );
+}
+
+#[test]
+fn test_cfg_field_names() {
+    // `baz` is disabled
+    assert_eq!(
+        OptionalFoo::FIELD_NAMES,
+        ["bar", "skipped", "wrapped", "double_wrapped"]
+    );
+}
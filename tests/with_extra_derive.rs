@@ -1,8 +1,7 @@
 use optional_struct::*;
 
 #[optional_struct]
-// TODO this does not work (conflicting implementations)
-#[derive(Debug, std::hash::Hash, Clone, PartialEq, Default)]
+#[derive(Debug, std::hash::Hash, ::core::clone::Clone, std::cmp::PartialEq, Default)]
 struct Config {
     delay: Option<u32>,
     path: String,